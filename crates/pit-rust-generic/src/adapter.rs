@@ -0,0 +1,221 @@
+//! Adapters between the sync and async forms of interface traits.
+//!
+//! The adapters always bridge the non-dyn-compatible traits (see
+//! [`FeatureFlags::dyn_compatible`]), which refer to each other by their
+//! `P<hex_id>` names: both the sync and the async traits must be in scope, for
+//! example by generating them in two modules and glob-importing both. Interfaces
+//! with untyped resources also need the `AnyResource` type (see
+//! [`crate::rt::any_resource`]), which should then be generated once in a third
+//! module imported by all of them.
+//!
+//! Resources passed across an adapter are wrapped in the opposite adapter, and
+//! borrowed resources are boxed, so the generated code requires `alloc`.
+
+use pit_core::{Arg, Interface};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{FeatureFlags, Params, alloc, args, awaited, method_impl, name, scoped};
+
+/// Returns the parameters of the sync and async traits.
+fn params(p: &Params) -> (Params, Params) {
+    let p = Params {
+        flags: FeatureFlags {
+            dyn_compatible: false,
+            ..p.flags
+        },
+        ..p.clone()
+    };
+    (
+        Params {
+            asyncness: None,
+            ..p.clone()
+        },
+        Params {
+            asyncness: Some(Default::default()),
+            ..p
+        },
+    )
+}
+
+/// Generates the shared adapter types.
+///
+/// This generates:
+/// - `Executor`, which drives futures to completion for synchronous callers
+/// - `Ready`, an executor for futures that complete when first polled
+/// - `SyncAdapter<T, X = Ready>`, implementing the async traits for a sync implementation `T`
+/// - `BlockOn<T, X>`, implementing the sync traits for an async implementation `T`
+/// - `Forward<T>`, implementing the traits of `T`'s target for a pointer `T`
+///
+/// This should be generated once per module, alongside the [`adapters`] implementations.
+pub fn wrappers(p: &Params) -> TokenStream {
+    let core = &p.core;
+    quote! {
+        /// Drives futures to completion for synchronous callers.
+        pub trait Executor{
+            /// Runs `f` to completion.
+            fn block_on<F: #core::future::Future>(&self, f: F) -> F::Output;
+        }
+        /// An executor for futures that complete when first polled, such as those of [`SyncAdapter`].
+        ///
+        /// Blocking on a pending future panics.
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::marker::Copy, #core::default::Default)]
+        pub struct Ready;
+        /// Implements the async interface traits for a sync implementation.
+        ///
+        /// Resources passed to the implementation are driven by the executor `X`.
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::default::Default)]
+        pub struct SyncAdapter<T, X = Ready>(pub T, pub X);
+        /// Implements the sync interface traits for an async implementation, driven by the executor `X`.
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::default::Default)]
+        pub struct BlockOn<T, X>(pub T, pub X);
+        /// Implements the interface traits of the target of a pointer.
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::default::Default)]
+        pub struct Forward<T>(pub T);
+        const _: () = {
+            impl Executor for Ready{
+                fn block_on<F: #core::future::Future>(&self, f: F) -> F::Output{
+                    let mut f = #core::pin::pin!(f);
+                    let mut cx = #core::task::Context::from_waker(#core::task::Waker::noop());
+                    match f.as_mut().poll(&mut cx){
+                        #core::task::Poll::Ready(a) => a,
+                        #core::task::Poll::Pending => #core::panic!("future was not ready when first polled"),
+                    }
+                }
+            }
+            impl<T> SyncAdapter<T>{
+                /// Wraps `t`, driving resources passed to it with [`Ready`].
+                pub fn new(t: T) -> Self{
+                    SyncAdapter(t, Ready)
+                }
+            }
+        };
+    }
+}
+
+/// Converts the value `v` of type `a` for the other side of an adapter, wrapping resources in `wrap`.
+fn bridge(a: &Arg, v: TokenStream, wrap: &Ident, alloc: &TokenStream) -> TokenStream {
+    let Arg::Resource {
+        ty,
+        nullable,
+        take,
+        ..
+    } = a
+    else {
+        return v;
+    };
+    if !matches!(ty, pit_core::ResTy::Of(_) | pit_core::ResTy::This) {
+        return v;
+    }
+    let f = match *take {
+        true => quote! {
+            |a| #wrap(a, self.1.clone())
+        },
+        false => quote! {
+            |a| #alloc::boxed::Box::new(#wrap(Forward(a), self.1.clone()))
+        },
+    };
+    match *nullable {
+        true => quote! {
+            #v.map(#f)
+        },
+        false => quote! {
+            (#f)(#v)
+        },
+    }
+}
+
+/// Generates the adapter implementations for a PIT interface.
+///
+/// This implements the async trait for `SyncAdapter`, the sync trait for `BlockOn`,
+/// and both traits for `Forward`; see [`wrappers`].
+///
+/// # Arguments
+///
+/// * `p` - Code generation parameters; [`Params::asyncness`] is ignored
+/// * `i` - The PIT interface to adapt
+///
+/// # Returns
+///
+/// A `TokenStream` containing the trait implementations.
+pub fn adapters(p: &Params, i: &Interface) -> TokenStream {
+    let (sp, ap) = params(&scoped(p, i));
+    let root = i.rid();
+    let core = &p.core;
+    let alloc = alloc(p);
+    let s = name(&sp, root);
+    let a = name(&ap, root);
+    let adapt = |p: &Params, wrap: Ident, unwrap: Ident| {
+        i.methods
+            .iter()
+            .map(|(m, sig)| {
+                let f = format_ident!("{m}");
+                let params = sig
+                    .params
+                    .iter()
+                    .zip(args(sig))
+                    .map(|(a, b)| bridge(a, quote! {#b}, &unwrap, &alloc));
+                let call = quote! {
+                    self.0.#f(#(#params),*)
+                };
+                let call = match p.asyncness {
+                    Some(_) => call,
+                    None => quote! {
+                        self.1.block_on(#call)
+                    },
+                };
+                let rets = (0..sig.rets.len())
+                    .map(|a| format_ident!("ret{a}"))
+                    .collect::<Vec<_>>();
+                let wrapped = sig
+                    .rets
+                    .iter()
+                    .zip(rets.iter())
+                    .map(|(a, b)| bridge(a, quote! {#b}, &wrap, &alloc));
+                let body = quote! {
+                    let (#(#rets),*) = #call?;
+                    #core::result::Result::Ok((#(#wrapped),*))
+                };
+                method_impl(p, m, sig, root, body)
+            })
+            .collect::<Vec<_>>()
+    };
+    let to_async = adapt(&ap, format_ident!("SyncAdapter"), format_ident!("BlockOn"));
+    let to_sync = adapt(&sp, format_ident!("BlockOn"), format_ident!("SyncAdapter"));
+    let forward = |p: &Params| {
+        i.methods
+            .iter()
+            .map(|(m, sig)| {
+                let f = format_ident!("{m}");
+                let args = args(sig);
+                let call = awaited(
+                    p,
+                    quote! {
+                        (*self.0).#f(#(#args),*)
+                    },
+                );
+                method_impl(p, m, sig, root, call)
+            })
+            .collect::<Vec<_>>()
+    };
+    let forward_sync = forward(&sp);
+    let forward_async = forward(&ap);
+    quote! {
+        impl<'bound,T: #s<'bound>,X: Executor + #core::clone::Clone + 'bound> #a<'bound> for SyncAdapter<T,X>{
+            type Error = T::Error;
+            #(#to_async)*
+        }
+        impl<'bound,T: #a<'bound>,X: Executor + #core::clone::Clone + 'bound> #s<'bound> for BlockOn<T,X>{
+            type Error = T::Error;
+            #(#to_sync)*
+        }
+        impl<'bound,T: #core::ops::DerefMut + 'bound> #s<'bound> for Forward<T> where T::Target: #s<'bound>{
+            type Error = <T::Target as #s<'bound>>::Error;
+            #(#forward_sync)*
+        }
+        impl<'bound,T: #core::ops::DerefMut + 'bound> #a<'bound> for Forward<T> where T::Target: #a<'bound>{
+            type Error = <T::Target as #a<'bound>>::Error;
+            #(#forward_async)*
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Build script helper generating the traits for a set of `.pit` files.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     pit_rust_generic::build::Generator::new()
+//!         .dir("pit/")
+//!         .async_variant(true)
+//!         .write_to_out_dir("pit.rs")
+//!         .unwrap();
+//! }
+//!
+//! // lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/pit.rs"));
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use pit_core::{Arg, Interface};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{FeatureFlags, GenError, Params, rt, try_interface};
+
+/// An error raised while generating code for a set of `.pit` files.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// A file or directory could not be read or written.
+    Io {
+        /// The path of the file or directory.
+        path: PathBuf,
+        /// The underlying error.
+        error: io::Error,
+    },
+    /// A file does not contain a valid PIT interface.
+    Parse {
+        /// The path of the file.
+        path: PathBuf,
+        /// The line of the error, starting at 1.
+        line: usize,
+        /// The column of the error, starting at 1.
+        column: usize,
+    },
+    /// An interface references a resource type not defined by any of the files.
+    Unresolved {
+        /// The path of the referencing file.
+        path: PathBuf,
+        /// The resource ID of the missing interface.
+        rid: [u8; 32],
+    },
+    /// An interface could not be generated.
+    Gen {
+        /// The path of the file.
+        path: PathBuf,
+        /// The underlying error.
+        error: GenError,
+    },
+    /// `OUT_DIR` is not set, as outside of build scripts.
+    NoOutDir,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            BuildError::Parse { path, line, column } => write!(
+                f,
+                "{}:{line}:{column}: failed to parse PIT interface",
+                path.display()
+            ),
+            BuildError::Unresolved { path, rid } => write!(
+                f,
+                "{}: unresolved resource type R{}",
+                path.display(),
+                hex::encode(rid)
+            ),
+            BuildError::Gen { path, error } => write!(f, "{}: {error}", path.display()),
+            BuildError::NoOutDir => f.write_str("OUT_DIR is not set"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Io { error, .. } => Some(error),
+            BuildError::Gen { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Generates the traits for a set of `.pit` files.
+///
+/// Every interface is emitted once, even if several files define it, and every
+/// resource type referenced by an interface must be defined by one of the files.
+///
+/// When an interface has untyped resources, the `AnyResource` type is emitted once
+/// (see [`crate::rt::any_resource`]), along with the `Resource` implementations of
+/// every interface when generating dyn-compatible traits.
+#[derive(Clone)]
+pub struct Generator {
+    params: Params,
+    dirs: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator {
+    /// Creates a generator with no files, generating sync traits for `::std`.
+    ///
+    /// Use [`Generator::params`] with [`Params::core`] set to `::core` to generate
+    /// traits for `no_std` crates, which then need `extern crate alloc`.
+    pub fn new() -> Self {
+        Self {
+            params: Params {
+                core: syn::parse_quote!(::std),
+                flags: Default::default(),
+                asyncness: None,
+                names: Default::default(),
+            },
+            dirs: vec![],
+            files: vec![],
+        }
+    }
+
+    /// Adds the `.pit` files directly inside `dir`.
+    pub fn dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Adds the `.pit` file at `path`.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the code generation parameters.
+    pub fn params(mut self, params: Params) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Sets the feature flags.
+    pub fn flags(mut self, flags: FeatureFlags) -> Self {
+        self.params.flags = flags;
+        self
+    }
+
+    /// Sets whether async traits are generated.
+    pub fn async_variant(mut self, asyncness: bool) -> Self {
+        self.params.asyncness = asyncness.then(Default::default);
+        self
+    }
+
+    /// Returns the paths of the `.pit` files, sorted within each directory.
+    fn paths(&self) -> Result<Vec<PathBuf>, BuildError> {
+        let mut paths = vec![];
+        for dir in self.dirs.iter() {
+            let io = |error| BuildError::Io {
+                path: dir.clone(),
+                error,
+            };
+            let mut found = vec![];
+            for e in fs::read_dir(dir).map_err(io)? {
+                let path = e.map_err(io)?.path();
+                if path.extension().is_some_and(|a| a == "pit") && path.is_file() {
+                    found.push(path);
+                }
+            }
+            found.sort();
+            paths.extend(found);
+        }
+        paths.extend(self.files.iter().cloned());
+        Ok(paths)
+    }
+
+    /// Generates the traits for every interface.
+    ///
+    /// When `rerun` is set, prints a `cargo:rerun-if-changed` line for every
+    /// directory and file read.
+    fn run(&self, rerun: bool) -> Result<TokenStream, BuildError> {
+        if rerun {
+            for dir in self.dirs.iter() {
+                println!("cargo:rerun-if-changed={}", dir.display());
+            }
+        }
+        let mut ifaces: BTreeMap<[u8; 32], (PathBuf, Interface)> = BTreeMap::new();
+        for path in self.paths()? {
+            if rerun {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+            let src = fs::read_to_string(&path).map_err(|error| BuildError::Io {
+                path: path.clone(),
+                error,
+            })?;
+            let i = match parse(&src) {
+                Ok(i) => i,
+                Err(o) => {
+                    return Err(BuildError::Parse {
+                        line: src[..o].matches('\n').count() + 1,
+                        column: o - src[..o].rfind('\n').map_or(0, |a| a + 1) + 1,
+                        path,
+                    });
+                }
+            };
+            ifaces.entry(i.rid()).or_insert((path, i));
+        }
+        let mut out = quote! {};
+        let mut untyped = false;
+        for (path, i) in ifaces.values() {
+            let refs = i.methods.values().flat_map(|s| s.params.iter().chain(s.rets.iter()));
+            for a in refs {
+                match a {
+                    Arg::Resource {
+                        ty: pit_core::ResTy::Of(rid),
+                        ..
+                    } if !ifaces.contains_key(rid) => {
+                        return Err(BuildError::Unresolved {
+                            path: path.clone(),
+                            rid: *rid,
+                        });
+                    }
+                    Arg::Resource {
+                        ty: pit_core::ResTy::None,
+                        ..
+                    } => untyped = true,
+                    _ => {}
+                }
+            }
+            let t = try_interface(&self.params, i).map_err(|error| BuildError::Gen {
+                path: path.clone(),
+                error,
+            })?;
+            out = quote! {
+                #out
+                #t
+            };
+        }
+        if untyped {
+            let a = rt::any_resource(&self.params);
+            out = quote! {
+                #out
+                #a
+            };
+            if self.params.flags.dyn_compatible {
+                for (_, i) in ifaces.values() {
+                    let r = rt::resource(&self.params, i);
+                    out = quote! {
+                        #out
+                        #r
+                    };
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Generates the traits for every interface.
+    pub fn generate(&self) -> Result<TokenStream, BuildError> {
+        self.run(false)
+    }
+
+    /// Writes the traits for every interface to `path`.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<(), BuildError> {
+        let path = path.as_ref();
+        let t = self.run(true)?;
+        fs::write(path, t.to_string()).map_err(|error| BuildError::Io {
+            path: path.to_owned(),
+            error,
+        })
+    }
+
+    /// Writes the traits for every interface to the file `name` in `OUT_DIR`,
+    /// returning its path.
+    pub fn write_to_out_dir(&self, name: impl AsRef<Path>) -> Result<PathBuf, BuildError> {
+        let dir = std::env::var_os("OUT_DIR").ok_or(BuildError::NoOutDir)?;
+        let path = Path::new(&dir).join(name);
+        self.write_to(&path)?;
+        Ok(path)
+    }
+}
+
+/// Parses a PIT interface, returning the byte offset of the error on failure.
+fn parse(src: &str) -> Result<Interface, usize> {
+    match pit_core::parse_interface(src) {
+        Ok((rest, i)) => match rest.trim_start() {
+            "" => Ok(i),
+            rest => Err(src.len() - rest.len()),
+        },
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(src.len() - e.input.len()),
+        Err(nom::Err::Incomplete(_)) => Err(src.len()),
+    }
+}
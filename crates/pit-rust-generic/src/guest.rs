@@ -0,0 +1,782 @@
+//! Glue for Rust WebAssembly guests talking to a PIT host.
+//!
+//! The generated glue always targets the dyn-compatible, synchronous form of the
+//! interface traits (see [`FeatureFlags::dyn_compatible`]), with
+//! [`core::convert::Infallible`] as the error type: failures on the host side trap
+//! instead of returning to the guest.
+//!
+//! ## ABI
+//!
+//! Resources are passed as `i32` handles; `0` denotes a null resource, so valid
+//! handles and slots are always non-zero. For an interface with resource ID `<hex>`,
+//! the module `pit/<hex>` provides:
+//! - `<method>(handle, params...) -> results` - Calls a method on a host resource
+//! - `~drop(handle)` - Releases an owned host handle
+//! - `~<salt>(slot) -> handle` - Wraps a slot of the guest's table into a host handle
+//!
+//! Handles passed as parameters are released by their sender once the call returns,
+//! unless they are owned. Handles returned are owned by the receiver, which releases
+//! borrowed ones once their borrow ends.
+//!
+//! The guest provides, for each salt:
+//! - `pit/<hex>/~<salt>/<method>(slot, params...) -> results` - Calls a method on a guest object
+//! - `pit/<hex>/~<salt>.drop(slot)` - Releases a guest slot
+//!
+//! Methods with more than one result return them through a `#[repr(C)]` struct.
+//!
+//! Slots are handles of a [`HandleTable`](crate::rt::handle_table): calls on a
+//! released slot, and releasing a slot twice, trap with the `HandleError` instead
+//! of touching freed memory. Slots of lent objects stay allocated until the host
+//! releases them, but calls through them trap once the borrow has ended.
+//!
+//! Borrowed resources returned by guest methods are borrowed from the object the
+//! method was called on: their borrow ends when that object is called again or
+//! released, as with Rust references. Calls reentering an object whose method is
+//! running, and releasing such an object, trap instead of aliasing it.
+//!
+//! Untyped resources cannot cross the boundary, as the host has no way to tell
+//! their interface: interfaces using them are rejected with a [`GenError`].
+
+use pit_core::{Arg, Interface, Sig};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{FeatureFlags, GenError, Params, alloc, arg, args, at, name, scoped, sig_with};
+
+/// Returns the parameters used for glue code generation.
+fn params(p: &Params) -> Params {
+    Params {
+        flags: FeatureFlags {
+            dyn_compatible: true,
+            signed: p.flags.signed,
+            ..Default::default()
+        },
+        asyncness: None,
+        ..p.clone()
+    }
+}
+
+/// Returns the resource ID referenced by `a`, if `a` is a resource.
+///
+/// The inner `Option` is `None` for untyped resources.
+fn res(a: &Arg, root: [u8; 32]) -> Option<Option<[u8; 32]>> {
+    match a {
+        Arg::Resource { ty, .. } => Some(match ty {
+            pit_core::ResTy::Of(a) => Some(*a),
+            pit_core::ResTy::This => Some(root),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Returns the `(nullable, take)` modifiers of `a`.
+pub(crate) fn modifiers(a: &Arg) -> (bool, bool) {
+    match a {
+        Arg::Resource { nullable, take, .. } => (*nullable, *take),
+        _ => (false, true),
+    }
+}
+
+/// Generates the identifier of the handle type for the interface with the given resource ID.
+///
+/// The name is the trait name (see [`name`]) followed by `Import`.
+pub fn import_name(p: &Params, rid: [u8; 32]) -> Ident {
+    format_ident!("{}Import", name(&params(p), rid))
+}
+
+/// Generates the ABI type of a value of type `a`.
+pub(crate) fn abi(p: &Params, a: &Arg) -> TokenStream {
+    let core = &p.core;
+    match a {
+        Arg::Resource { .. } => quote! {#core::primitive::u32},
+        a => arg(p, a, [0; 32]),
+    }
+}
+
+/// Generates a tuple of `a`, or its only element if there is exactly one.
+pub(crate) fn tuple(a: impl IntoIterator<Item = TokenStream>) -> TokenStream {
+    let mut a = a.into_iter().collect::<Vec<_>>();
+    match a.len() {
+        1 => a.remove(0),
+        _ => quote! {
+            (#(#a),*)
+        },
+    }
+}
+
+/// Checks that every method of `i` can cross the PIT boundary.
+///
+/// Fails on the constructs [`try_interface`](crate::try_interface) fails on, and
+/// on untyped resources.
+fn check(p: &Params, i: &Interface) -> Result<(), GenError> {
+    crate::check(p, i)?;
+    let root = i.rid();
+    for (m, s) in i.methods.iter() {
+        if let Some(a) = s
+            .params
+            .iter()
+            .chain(s.rets.iter())
+            .find(|a| res(a, root) == Some(None))
+        {
+            return Err(GenError::UnsupportedResource {
+                method: Some(m.clone()),
+                arg: a.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Generates an expression lowering the owned value `v` of type `a` to its ABI representation.
+///
+/// Borrowed resources are borrowed from the guest object in the slot `parent` of the
+/// interface `root`, and are lowered with `lower_child`, which ends their borrow along
+/// with the borrow of `parent`.
+fn lower(
+    p: &Params,
+    a: &Arg,
+    root: [u8; 32],
+    v: &TokenStream,
+    parent: &TokenStream,
+) -> TokenStream {
+    let (nullable, take) = modifiers(a);
+    let Some(r) = res(a, root) else {
+        return quote! {#v};
+    };
+    let Some(r) = r else {
+        unreachable!("untyped resources are rejected by `check`")
+    };
+    let imp = import_name(p, r);
+    let this = import_name(p, root);
+    let f = match take {
+        true => quote! {#imp::lower},
+        // SAFETY: the borrow ends along with the borrow of the parent.
+        false => quote! {
+            (|a| unsafe{
+                #imp::lower_child(a, |f, child| #this::adopt(#parent, f, child))
+            })
+        },
+    };
+    match nullable {
+        true => quote! {
+            #v.map_or(0, #f)
+        },
+        false => quote! {
+            #f(#v)
+        },
+    }
+}
+
+/// Generates an expression lifting the ABI value `h` to a value of type `a`.
+///
+/// Borrowed resources are held by `parent`, the handle they are borrowed from, until
+/// its next call or its release.
+fn lift(p: &Params, a: &Arg, root: [u8; 32], h: &TokenStream, parent: &TokenStream) -> TokenStream {
+    let core = &p.core;
+    let (nullable, take) = modifiers(a);
+    let Some(r) = res(a, root) else {
+        return quote! {#h};
+    };
+    let Some(r) = r else {
+        unreachable!("untyped resources are rejected by `check`")
+    };
+    let imp = import_name(p, r);
+    let z = name(p, r);
+    let v = match take {
+        true => quote! {#imp::lift(#h)},
+        // SAFETY: the parent frees the handle at its next call, once the borrow has ended.
+        false => quote! {
+            #imp::lift_ref(#h).map(|a| unsafe{
+                &mut *#parent.hold(a) as &mut (dyn #z<'static,Error = #core::convert::Infallible> + 'static)
+            })
+        },
+    };
+    match nullable {
+        true => v,
+        false => quote! {
+            #core::option::Option::expect(#v, "null resource in non-nullable position")
+        },
+    }
+}
+
+/// Generates a `#[repr(C)]` struct carrying the results of a method, if it has more than one.
+pub(crate) fn rets_struct(p: &Params, m: &str, s: &Sig) -> Option<(Ident, TokenStream)> {
+    if s.rets.len() <= 1 {
+        return None;
+    }
+    let n = format_ident!("{m}_rets");
+    let fields = s.rets.iter().enumerate().map(|(i, a)| {
+        let a = abi(&at(p, s, &format!("r{i}")), a);
+        let i = format_ident!("r{i}");
+        quote! {
+            pub #i: #a
+        }
+    });
+    let t = quote! {
+        #[allow(non_camel_case_types)]
+        #[repr(C)]
+        pub struct #n{
+            #(#fields),*
+        }
+    };
+    Some((n, t))
+}
+
+/// Generates import shims for consuming a host-provided PIT interface.
+///
+/// # Generated Code Structure
+///
+/// The generated code includes:
+/// - A handle type named `<trait>Import` wrapping a host handle, which releases
+///   the handle with `~drop` when dropped
+/// - `extern` declarations for the methods of the `pit/<hex>` import module
+/// - An implementation of the dyn-compatible trait for the handle type
+/// - A guest-side `HandleTable` of objects handed to the host, used by the `lower` and `lend`
+///   conversions; the host reaches these objects through `~<salt>`
+///
+/// Every interface referenced as a resource must also have its shims generated
+/// in the same module, as conversions go through the handle type of that interface.
+/// The `HandleTable` type must be generated once in the same module with
+/// [`crate::rt::handle_table`].
+///
+/// # Arguments
+///
+/// * `p` - Code generation parameters; only [`Params::core`] and [`FeatureFlags::signed`] are used
+/// * `i` - The PIT interface to generate shims for
+/// * `salt` - The salt identifying this guest's table to the host
+///
+/// # Returns
+///
+/// A `TokenStream` containing the handle type and its implementation, or a [`GenError`]
+/// if a method uses an untyped or otherwise unsupported resource.
+pub fn imports(p: &Params, i: &Interface, salt: &str) -> Result<TokenStream, GenError> {
+    let p = &scoped(&params(p), i);
+    check(p, i)?;
+    let root = i.rid();
+    let core = &p.core;
+    let alloc = alloc(p);
+    let x = name(p, root);
+    let imp = import_name(p, root);
+    let module = format!("pit/{}", hex::encode(root));
+    let new = format!("~{salt}");
+    let obj = quote! {
+        dyn #x<'static,Error = #core::convert::Infallible> + 'static
+    };
+    let mut structs = Vec::new();
+    let decls = i
+        .methods
+        .iter()
+        .map(|(m, s)| {
+            let f = format_ident!("{m}");
+            let params = s.params.iter().enumerate().map(|(a, b)| {
+                let b = abi(&at(p, s, &format!("p{a}")), b);
+                let a = format_ident!("arg{a}");
+                quote! {
+                    #a: #b
+                }
+            });
+            let ret = match rets_struct(p, m, s) {
+                Some((n, t)) => {
+                    structs.push(t);
+                    quote! {
+                        -> #n
+                    }
+                }
+                None => {
+                    let r = tuple(
+                        s.rets
+                            .iter()
+                            .enumerate()
+                            .map(|(i, a)| abi(&at(p, s, &format!("r{i}")), a)),
+                    );
+                    quote! {
+                        -> #r
+                    }
+                }
+            };
+            quote! {
+                #[link_name = #m]
+                pub fn #f(this: #core::primitive::u32, #(#params),*) #ret;
+            }
+        })
+        .collect::<Vec<_>>();
+    let methods = i.methods.iter().map(|(m, s)| {
+        let f = format_ident!("{m}");
+        let args = args(s);
+        let sig = sig_with(p, s, root, &args);
+        let owned = s
+            .params
+            .iter()
+            .zip(args.iter())
+            .filter(|(a, _)| modifiers(a).1)
+            .map(|(a, v)| {
+                let l = lower(p, a, root, &quote! {#v}, &quote! {0});
+                quote! {
+                    let #v = #l;
+                }
+            });
+        let mut call = quote! {
+            unsafe{
+                methods::#f(this, #(#args),*)
+            }
+        };
+        for (a, v) in s.params.iter().zip(args.iter()).rev() {
+            if modifiers(a).1 {
+                continue;
+            }
+            let Some(Some(r)) = res(a, root) else {
+                unreachable!("untyped resources are rejected by `check`")
+            };
+            let y = import_name(p, r);
+            let v2 = match modifiers(a).0 {
+                true => quote! {#v},
+                false => quote! {#core::option::Option::Some(#v)},
+            };
+            call = quote! {
+                #y::lend(#v2, |#v| #call)
+            };
+        }
+        let rets = (0..s.rets.len())
+            .map(|a| format_ident!("r{a}"))
+            .collect::<Vec<_>>();
+        let bind = match rets_struct(p, m, s) {
+            Some((n, _)) => quote! {
+                let methods::#n{#(#rets),*} = #call;
+            },
+            None => {
+                let rets = tuple(rets.iter().map(|a| quote! {#a}));
+                quote! {
+                    let #rets = #call;
+                }
+            }
+        };
+        let lifted = tuple(
+            s.rets
+                .iter()
+                .zip(rets.iter())
+                .map(|(a, v)| lift(p, a, root, &quote! {#v}, &quote! {self})),
+        );
+        quote! {
+            fn #f #sig {
+                // The results borrowed from earlier calls are no longer borrowed.
+                self.free();
+                let this = self.handle;
+                #(#owned)*
+                #bind
+                #core::result::Result::Ok(#lifted)
+            }
+        }
+    });
+    Ok(quote! {
+        pub struct #imp{
+            handle: #core::primitive::u32,
+            // Whether the handle is released when dropped.
+            owned: #core::primitive::bool,
+            // The handles of the results borrowed from the last call.
+            held: #alloc::vec::Vec<*mut dyn #core::any::Any>,
+        }
+        const _: () = {
+            mod methods{
+                #(#structs)*
+                // Other interfaces import methods with the same names from other modules.
+                #[allow(clashing_extern_declarations)]
+                #[link(wasm_import_module = #module)]
+                unsafe extern "C" {
+                    #(#decls)*
+                }
+            }
+            #[link(wasm_import_module = #module)]
+            unsafe extern "C" {
+                #[link_name = "~drop"]
+                fn pit_drop(this: #core::primitive::u32);
+                #[link_name = #new]
+                fn pit_new(slot: #core::primitive::u32) -> #core::primitive::u32;
+            }
+            struct Slot{
+                // `None` once the borrow of a lent object has ended.
+                ptr: #core::option::Option<*mut (#obj)>,
+                owned: #core::primitive::bool,
+                // Whether a method of the object is running.
+                busy: #core::primitive::bool,
+                // The slots of objects borrowed from this one, with their `invalidate` functions.
+                children: #alloc::vec::Vec<(fn(#core::primitive::u32), #core::primitive::u32)>,
+            }
+            struct Table(#core::cell::UnsafeCell<HandleTable<Slot>>);
+            // SAFETY: WebAssembly guests are single-threaded.
+            unsafe impl #core::marker::Sync for Table{}
+            static TABLE: Table = Table(#core::cell::UnsafeCell::new(HandleTable::new()));
+            fn table() -> &'static mut HandleTable<Slot>{
+                // SAFETY: the table is never borrowed across calls into user code.
+                unsafe{
+                    &mut *TABLE.0.get()
+                }
+            }
+            fn insert(ptr: *mut (#obj), owned: #core::primitive::bool) -> #core::primitive::u32{
+                table().insert(Slot{
+                    ptr: #core::option::Option::Some(ptr),
+                    owned,
+                    busy: false,
+                    children: #alloc::vec::Vec::new(),
+                })
+            }
+            fn remove(slot: #core::primitive::u32) -> Slot{
+                match table().take(slot){
+                    #core::result::Result::Ok(a) => a,
+                    #core::result::Result::Err(e) => #core::panic!("{}", e),
+                }
+            }
+            impl #core::ops::Drop for #imp{
+                fn drop(&mut self){
+                    self.free();
+                    if self.owned{
+                        unsafe{
+                            pit_drop(self.handle)
+                        }
+                    }
+                }
+            }
+            impl #imp{
+                /// Wraps an owned host handle.
+                ///
+                /// # Safety
+                ///
+                /// `handle` must be a non-zero owned handle to a resource of this interface.
+                pub unsafe fn from_raw(handle: #core::primitive::u32) -> Self{
+                    Self::new(handle, true)
+                }
+                fn new(handle: #core::primitive::u32, owned: #core::primitive::bool) -> Self{
+                    Self{
+                        handle,
+                        owned,
+                        held: #alloc::vec::Vec::new(),
+                    }
+                }
+                /// Returns the host handle without releasing it.
+                pub fn into_raw(self) -> #core::primitive::u32{
+                    let mut this = #core::mem::ManuallyDrop::new(self);
+                    this.free();
+                    this.handle
+                }
+                /// Lifts an owned host handle, returning `None` for a null handle.
+                pub fn lift(handle: #core::primitive::u32) -> #core::option::Option<#alloc::boxed::Box<#obj>>{
+                    match handle{
+                        0 => #core::option::Option::None,
+                        handle => #core::option::Option::Some(#alloc::boxed::Box::new(Self::new(handle, true))),
+                    }
+                }
+                /// Lifts a host handle returned as a borrowed resource, returning `None` for a
+                /// null handle.
+                ///
+                /// The handle is owned by the guest, and released when the wrapper is dropped,
+                /// which should happen once the borrow ends.
+                pub fn lift_ref(handle: #core::primitive::u32) -> #core::option::Option<Self>{
+                    match handle{
+                        0 => #core::option::Option::None,
+                        handle => #core::option::Option::Some(Self::new(handle, true)),
+                    }
+                }
+                /// Wraps a borrowed host handle for the duration of a call, returning `None` for a null handle.
+                ///
+                /// The handle is not released when the wrapper is dropped.
+                pub fn borrow(handle: #core::primitive::u32) -> #core::option::Option<Self>{
+                    match handle{
+                        0 => #core::option::Option::None,
+                        handle => #core::option::Option::Some(Self::new(handle, false)),
+                    }
+                }
+                /// Keeps `value` until the next call through this handle or its release,
+                /// returning a pointer to it valid until then.
+                #[doc(hidden)]
+                pub fn hold<T: 'static>(&mut self, value: T) -> *mut T{
+                    let value = #alloc::boxed::Box::into_raw(#alloc::boxed::Box::new(value));
+                    self.held.push(value);
+                    value
+                }
+                /// Drops the values kept by [`Self::hold`].
+                fn free(&mut self){
+                    for value in #core::mem::take(&mut self.held){
+                        #core::mem::drop(unsafe{
+                            #alloc::boxed::Box::from_raw(value)
+                        });
+                    }
+                }
+                /// Hands an owned guest object to the host, returning an owned host handle.
+                pub fn lower(value: #alloc::boxed::Box<#obj>) -> #core::primitive::u32{
+                    let slot = insert(#alloc::boxed::Box::into_raw(value), true);
+                    unsafe{
+                        pit_new(slot)
+                    }
+                }
+                /// Hands a borrowed guest object to the host, returning an owned host handle.
+                ///
+                /// # Safety
+                ///
+                /// Until the host releases the handle, `value` must stay valid and must only be
+                /// accessed through the handle. Use [`Self::lend`] to lend an object for the
+                /// duration of a call instead.
+                pub unsafe fn lower_ref(value: &mut (#obj)) -> #core::primitive::u32{
+                    let slot = insert(value, false);
+                    unsafe{
+                        pit_new(slot)
+                    }
+                }
+                /// Hands a borrowed guest object to the host, passing its slot and the function
+                /// ending its borrow to `adopt`, and returning an owned host handle.
+                ///
+                /// # Safety
+                ///
+                /// `value` must stay valid and must only be accessed through the handle until
+                /// the host releases the handle or the function passed to `adopt` is called.
+                #[doc(hidden)]
+                pub unsafe fn lower_child(value: &mut (#obj), adopt: impl #core::ops::FnOnce(fn(#core::primitive::u32), #core::primitive::u32)) -> #core::primitive::u32{
+                    let slot = insert(value, false);
+                    adopt(Self::invalidate, slot);
+                    unsafe{
+                        pit_new(slot)
+                    }
+                }
+                /// Lends a guest object to the host for the duration of `f`.
+                ///
+                /// Calls made by the host through the slot after `f` returns trap.
+                ///
+                /// `f` receives a host handle, or `0` if `value` is `None`.
+                pub fn lend<R>(value: #core::option::Option<&mut (#obj)>, f: impl #core::ops::FnOnce(#core::primitive::u32) -> R) -> R{
+                    let #core::option::Option::Some(value) = value else{
+                        return f(0);
+                    };
+                    let slot = insert(value, false);
+                    let handle = Self::new(unsafe{
+                        pit_new(slot)
+                    }, true);
+                    let r = f(handle.handle);
+                    #core::mem::drop(handle);
+                    Self::invalidate(slot);
+                    r
+                }
+                /// Ends the borrow of the guest object in `slot`, and of the objects borrowed from it.
+                ///
+                /// The host may release the slot later, or already did.
+                #[doc(hidden)]
+                pub fn invalidate(slot: #core::primitive::u32){
+                    let children = match table().borrow_mut(slot){
+                        #core::result::Result::Ok(s) if s.busy => #core::panic!("PIT slot {:#x} invalidated while in use", slot),
+                        #core::result::Result::Ok(s) => {
+                            s.ptr = #core::option::Option::None;
+                            #core::mem::take(&mut s.children)
+                        }
+                        #core::result::Result::Err(_) => return,
+                    };
+                    for (f, child) in children{
+                        f(child);
+                    }
+                }
+                /// Ties the borrow of `child`, ended by `f`, to the borrow of the guest object in `slot`.
+                ///
+                /// A `slot` of `0` denotes a guest object not reachable by the host.
+                #[doc(hidden)]
+                pub fn adopt(slot: #core::primitive::u32, f: fn(#core::primitive::u32), child: #core::primitive::u32){
+                    if let #core::result::Result::Ok(s) = table().borrow_mut(slot){
+                        s.children.push((f, child));
+                    }
+                }
+                /// Marks the guest object in `slot` as in use by a call, and returns it.
+                ///
+                /// The borrows of objects borrowed from it by earlier calls end.
+                ///
+                /// # Safety
+                ///
+                /// The returned pointer must only be used until the matching call to [`Self::leave`].
+                #[doc(hidden)]
+                pub unsafe fn enter(slot: #core::primitive::u32) -> *mut (#obj){
+                    let (ptr, children) = match table().borrow_mut(slot){
+                        #core::result::Result::Ok(s) if s.busy => #core::panic!("PIT slot {:#x} reentered while in use", slot),
+                        #core::result::Result::Ok(Slot{ptr: #core::option::Option::Some(ptr), busy, children, ..}) => {
+                            *busy = true;
+                            (*ptr, #core::mem::take(children))
+                        }
+                        #core::result::Result::Ok(_) => #core::panic!("PIT slot {:#x} used after its borrow ended", slot),
+                        #core::result::Result::Err(e) => #core::panic!("{}", e),
+                    };
+                    for (f, child) in children{
+                        f(child);
+                    }
+                    ptr
+                }
+                /// Ends the call that entered `slot`.
+                #[doc(hidden)]
+                pub fn leave(slot: #core::primitive::u32){
+                    if let #core::result::Result::Ok(s) = table().borrow_mut(slot){
+                        s.busy = false;
+                    }
+                }
+                /// Releases `slot`, dropping the object if it is owned.
+                #[doc(hidden)]
+                pub fn release(slot: #core::primitive::u32){
+                    if let #core::result::Result::Ok(Slot{busy: true, ..}) = table().borrow(slot){
+                        #core::panic!("PIT slot {:#x} released while in use", slot);
+                    }
+                    let s = remove(slot);
+                    for (f, child) in s.children{
+                        f(child);
+                    }
+                    if let (#core::option::Option::Some(ptr), true) = (s.ptr, s.owned){
+                        #core::mem::drop(unsafe{
+                            #alloc::boxed::Box::from_raw(ptr)
+                        });
+                    }
+                }
+            }
+            impl<'bound> #x<'bound> for #imp where 'bound: 'static{
+                type Error = #core::convert::Infallible;
+                #(#methods)*
+            }
+        };
+    })
+}
+
+/// Generates export glue for implementing a PIT interface in a Rust guest.
+///
+/// # Generated Code Structure
+///
+/// The generated code includes:
+/// - An exported `pit/<hex>/~<salt>/<method>` function per method, dispatching to the
+///   guest object in the given slot
+/// - An exported `pit/<hex>/~<salt>.drop` function releasing a slot, dropping owned objects
+/// - A `From<ty>` implementation for the handle type, handing a value of the user type
+///   to the host
+///
+/// Guest objects are owned as `Box<dyn P<hex>>` by the table emitted by [`imports`], which
+/// must be generated for the same interface and salt in the same module.
+/// Resources are marshaled as in [`imports`]: owned resources are lifted and lowered
+/// with ownership, borrowed resources only for the duration of the call.
+///
+/// # Arguments
+///
+/// * `p` - Code generation parameters; only [`Params::core`] and [`FeatureFlags::signed`] are used
+/// * `i` - The PIT interface to generate exports for
+/// * `salt` - The salt identifying this guest's table to the host
+/// * `ty` - The user type implementing the interface
+///
+/// # Returns
+///
+/// A `TokenStream` containing the exported functions, or a [`GenError`] if a method
+/// uses an untyped or otherwise unsupported resource.
+pub fn exports(
+    p: &Params,
+    i: &Interface,
+    salt: &str,
+    ty: &syn::Path,
+) -> Result<TokenStream, GenError> {
+    let p = &scoped(&params(p), i);
+    check(p, i)?;
+    let root = i.rid();
+    let core = &p.core;
+    let alloc = alloc(p);
+    let imp = import_name(p, root);
+    let prefix = format!("pit/{}/~{salt}", hex::encode(root));
+    let drop = format!("{prefix}.drop");
+    let methods = i.methods.iter().map(|(m, s)| {
+        let export = format!("{prefix}/{m}");
+        let f = format_ident!("{m}");
+        let e = format_ident!("export_{m}");
+        let args = (0..s.params.len())
+            .map(|a| format_ident!("arg{a}"))
+            .collect::<Vec<_>>();
+        let params = s.params.iter().zip(args.iter()).enumerate().map(|(i, (a, v))| {
+            let a = abi(&at(p, s, &format!("p{i}")), a);
+            quote! {
+                #v: #a
+            }
+        });
+        let lifted = s.params.iter().zip(args.iter()).map(|(a, v)| {
+            let (nullable, take) = modifiers(a);
+            match (take, res(a, root)) {
+                (false, Some(Some(r))) => {
+                    let y = import_name(p, r);
+                    let z = name(p, r);
+                    let b = match nullable {
+                        true => quote! {
+                            #v.as_mut().map(|a| a as &mut (dyn #z<'static,Error = #core::convert::Infallible> + 'static))
+                        },
+                        false => quote! {
+                            #core::option::Option::expect(#v.as_mut(), "null resource in non-nullable position")
+                        },
+                    };
+                    quote! {
+                        let mut #v = #y::borrow(#v);
+                        let #v = #b;
+                    }
+                }
+                _ => {
+                    let l = lift(p, a, root, &quote! {#v}, &quote! {this});
+                    quote! {
+                        let #v = #l;
+                    }
+                }
+            }
+        });
+        let rets = (0..s.rets.len())
+            .map(|a| format_ident!("r{a}"))
+            .collect::<Vec<_>>();
+        let lowered = s.rets.iter().zip(rets.iter()).map(|(a, v)| {
+            let l = lower(p, a, root, &quote! {#v}, &quote! {slot});
+            quote! {
+                let #v = #l;
+            }
+        });
+        let (ret, st, out) = match rets_struct(p, m, s) {
+            Some((n, t)) => (
+                quote! {#n},
+                t,
+                quote! {
+                    #n{#(#rets),*}
+                },
+            ),
+            None => (
+                tuple(
+                    s.rets
+                        .iter()
+                        .enumerate()
+                        .map(|(i, a)| abi(&at(p, s, &format!("r{i}")), a)),
+                ),
+                quote! {},
+                tuple(rets.iter().map(|a| quote! {#a})),
+            ),
+        };
+        let pat = tuple(rets.iter().map(|a| quote! {#a}));
+        quote! {
+            #st
+            #[unsafe(export_name = #export)]
+            extern "C" fn #e(slot: #core::primitive::u32, #(#params),*) -> #ret{
+                // SAFETY: the object stays in its slot until the host releases it, which
+                // traps until `leave`.
+                let this = unsafe{
+                    &mut *#imp::enter(slot)
+                };
+                #(#lifted)*
+                let #pat = match this.#f(#(#args),*){
+                    #core::result::Result::Ok(r) => r,
+                    #core::result::Result::Err(e) => match e{},
+                };
+                #(#lowered)*
+                #imp::leave(slot);
+                #out
+            }
+        }
+    });
+    Ok(quote! {
+        const _: () = {
+            #(#methods)*
+            #[unsafe(export_name = #drop)]
+            extern "C" fn release(slot: #core::primitive::u32){
+                #imp::release(slot)
+            }
+            impl #core::convert::From<#ty> for #imp{
+                fn from(value: #ty) -> Self{
+                    Self::new(#imp::lower(#alloc::boxed::Box::new(value)), true)
+                }
+            }
+        };
+    })
+}
@@ -11,6 +11,18 @@
 //! - [`interface`] - Generates a complete Rust trait from a PIT [`Interface`]
 //! - [`sig`] - Generates a method signature from a PIT [`Sig`]
 //! - [`arg`] - Generates a type expression from a PIT [`Arg`]
+//! - [`try_interface`], [`try_sig`], [`try_arg`] - Fallible versions, returning a [`GenError`]
+//!   for constructs the infallible versions replace with `Infallible`
+//! - [`guest::imports`] - Generates WebAssembly guest shims for host-provided interfaces
+//! - [`guest::exports`] - Generates WebAssembly guest exports for interfaces implemented in Rust
+//! - [`mock::mock`] - Generates mock implementations for unit tests
+//! - [`adapter::adapters`] - Generates adapters between the sync and async traits
+//! - [`rpc::rpc`] - Generates binary RPC clients and dispatchers over a user-provided transport
+//! - [`rt::handle_table`] - Generates the generational handle table used by the guest glue
+//! - [`rt::any_resource`] - Generates the `AnyResource` type of untyped resources
+//! - `traced::traced` - Generates tracing decorators (requires the `tracing` feature)
+//! - `build::Generator` - Generates the traits for a directory of `.pit` files from build
+//!   scripts (requires the `std` feature)
 //!
 //! ## Example
 //!
@@ -23,6 +35,7 @@
 //!     core: syn::parse_quote!(::core),
 //!     flags: Default::default(),
 //!     asyncness: None,
+//!     names: Default::default(),
 //! };
 //! let tokens = interface(&params, &iface);
 //! ```
@@ -33,23 +46,47 @@
 //! - `unstable-pcode` - Enable pcode expression support
 //! - `unstable-sdkcode` - Combined SDK and pcode support
 //! - `unstable-generics` - Enable generic parameter support
+//! - `tracing` - Enable the `traced` module, whose output depends on the `tracing` crate
+//! - `std` - Enable the `build` module
 
-use pit_core::{Arg, Interface, Sig};
-use proc_macro2::TokenStream;
+use std::collections::BTreeMap;
+
+use pit_core::{Arg, Attr, Interface, Sig};
+use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 use syn::token::Async;
 
+pub mod adapter;
+#[cfg(feature = "std")]
+pub mod build;
+pub mod guest;
+pub mod mock;
+pub mod rpc;
+pub mod rt;
+#[cfg(feature = "tracing")]
+pub mod traced;
+
 /// Configuration parameters for code generation.
 ///
 /// Controls how Rust code is generated from PIT interfaces.
+#[derive(Clone)]
 pub struct Params {
     /// Path to the core library (e.g., `::core` or `::std`).
     /// Used as the prefix for standard library types in generated code.
+    ///
+    /// Boxed types, such as the error of the `Transport` variant of the generated error
+    /// enums, are taken from `alloc` when this is `::core`, which requires
+    /// `extern crate alloc` in the consuming crate.
     pub core: syn::Path,
     /// Feature flags controlling code generation behavior.
     pub flags: FeatureFlags,
     /// If `Some`, generates async trait methods.
     pub asyncness: Option<Async>,
+    /// Readable trait names, keyed by interface resource ID.
+    ///
+    /// Takes precedence over the `name` annotation of the interface.
+    /// See [`readable_name`].
+    pub names: BTreeMap<[u8; 32], Ident>,
 }
 
 /// Feature flags controlling advanced code generation options.
@@ -60,9 +97,123 @@ pub struct Params {
 pub struct FeatureFlags {
     /// Enable Rust's specialization feature.
     ///
-    /// When enabled, generates default trait implementations that can be specialized.
+    /// When enabled, generates a `default impl` of the trait for every type, whose methods
+    /// return `P<hex_id>Error::Unimplemented`, so implementations may omit methods. The trait's
+    /// `Error` type must then implement `From<P<hex_id>Error>`, and defaults to it.
+    ///
+    /// Methods returning `impl Trait`, which are the methods of non-dyn-compatible traits
+    /// returning resources, cannot be specialized and get no default.
+    ///
+    /// Async methods cannot be specialized either, so they return boxed futures, as
+    /// with [`FeatureFlags::dyn_compatible`], instead of being `async fn`s.
+    ///
     /// Requires `#![feature(specialization)]` in the consuming crate.
     pub specialization: bool,
+    /// Generate dyn-compatible (object-safe) traits.
+    ///
+    /// When enabled, owned resources are passed as `Box<dyn P<hex><'bound, Error = Self::Error>>`
+    /// and borrowed resources as `&mut dyn P<hex><'bound, Error = Self::Error>`, so the generated
+    /// trait can be used as `dyn P<hex><'bound, Error = E>` with a fixed error type `E`.
+    /// Async methods return boxed futures instead of using `async fn`.
+    ///
+    /// Boxes are taken from `alloc` when [`Params::core`] is `::core`, which requires
+    /// `extern crate alloc` in the consuming crate.
+    pub dyn_compatible: bool,
+    /// Generate forwarding implementations for smart pointers.
+    ///
+    /// When enabled, [`interface`] also implements the trait for `&mut T` and `Box<T>`,
+    /// and, when [`Params::core`] is `::std`, for `Rc<RefCell<T>>` and `Arc<Mutex<T>>`,
+    /// forwarding every method to `T`.
+    ///
+    /// The `Rc<RefCell<T>>` and `Arc<Mutex<T>>` implementations are omitted for interfaces
+    /// with methods returning values that would borrow from the guard: any resource
+    /// unless [`FeatureFlags::dyn_compatible`] is set, and borrowed resources otherwise.
+    pub forwarding: bool,
+    /// Map `I32` and `I64` to `i32` and `i64` instead of `u32` and `u64`.
+    ///
+    /// This is the default for every interface; it can be overridden with a `[sign=s]`
+    /// (signed) or `[sign=u]` (unsigned) annotation on the interface or on a method,
+    /// and for a single value with `[p0.sign=...]` (first parameter) or `[r0.sign=...]`
+    /// (first result) annotations on the method.
+    pub signed: bool,
+}
+
+/// Returns the path used for `alloc` types (such as `Box`) in generated code.
+///
+/// This is `::alloc` when [`Params::core`] names `core`, and [`Params::core`] itself otherwise.
+fn alloc(p: &Params) -> TokenStream {
+    let core = &p.core;
+    match core.segments.last() {
+        Some(a) if a.ident == "core" => quote! {::alloc},
+        _ => quote! {#core},
+    }
+}
+
+/// Generates the identifier of the trait for the interface with the given resource ID.
+///
+/// The name is `P<hex_id>`, with `S` inserted when specialization is enabled,
+/// `D` when dyn-compatible traits are generated and `async` for async traits.
+pub fn name(p: &Params, rid: [u8; 32]) -> Ident {
+    let x = hex::encode(&rid);
+    format_ident!(
+        "P{}{}{}{x}",
+        match p.flags.specialization {
+            false => "",
+            true => "S",
+        },
+        match p.flags.dyn_compatible {
+            false => "",
+            true => "D",
+        },
+        match p.asyncness.as_ref() {
+            None => "",
+            Some(_) => "async",
+        }
+    )
+}
+
+/// Returns the value of the annotation `name` in `ann`, if present.
+fn ann<'a>(ann: &'a [Attr], name: &str) -> Option<&'a str> {
+    ann.iter()
+        .find(|a| a.name == name)
+        .map(|a| a.value.as_str())
+}
+
+/// Returns `p` with [`FeatureFlags::signed`] overridden by the annotation `key` in `ann`, if present.
+fn signed(p: &Params, ann: &[Attr], key: &str) -> Params {
+    let mut p = p.clone();
+    match self::ann(ann, key) {
+        Some("s" | "signed") => p.flags.signed = true,
+        Some("u" | "unsigned") => p.flags.signed = false,
+        _ => {}
+    }
+    p
+}
+
+/// Returns the parameters for generating the members of `i`, applying its `sign` annotation.
+pub(crate) fn scoped(p: &Params, i: &Interface) -> Params {
+    signed(p, &i.ann, "sign")
+}
+
+/// Returns the parameters for the value at `pos` of `s`, such as `p0` for the first
+/// parameter or `r0` for the first result, applying `sign` annotations.
+pub(crate) fn at(p: &Params, s: &Sig, pos: &str) -> Params {
+    signed(&signed(p, &s.ann, "sign"), &s.ann, &format!("{pos}.sign"))
+}
+
+/// Returns the readable trait name for an interface, if any.
+///
+/// The name is taken from [`Params::names`], or from a `[name=...]` annotation on
+/// the interface. Readable names do not depend on the generation mode, so
+/// interfaces generated in several modes should be placed in separate modules.
+///
+/// Annotations that are not valid identifiers are ignored; [`try_interface`]
+/// reports them as [`GenError::InvalidName`].
+pub fn readable_name(p: &Params, i: &Interface) -> Option<Ident> {
+    if let Some(a) = p.names.get(&i.rid()) {
+        return Some(a.clone());
+    }
+    ann(&i.ann, "name").and_then(|a| syn::parse_str::<Ident>(a).ok())
 }
 
 /// Generates a Rust type expression from a PIT argument type.
@@ -79,12 +230,33 @@ pub struct FeatureFlags {
 /// # Returns
 ///
 /// A `TokenStream` containing the Rust type expression.
+///
+/// Untyped resources are passed as `AnyResource`, which must be in scope, for example
+/// by generating it in the same module with [`rt::any_resource`].
 pub fn arg(p: &Params, a: &Arg, root: [u8; 32]) -> TokenStream {
+    let life = p.asyncness.as_ref().map(|_| quote! {'life});
+    arg_in(p, a, root, &quote! {Self::Error}, life.as_ref())
+}
+
+/// Generates a Rust type expression from a PIT argument type, with resources using the
+/// error type `e` and borrowed dyn-compatible resources using the lifetime `life`.
+pub(crate) fn arg_in(
+    p: &Params,
+    a: &Arg,
+    root: [u8; 32],
+    e: &TokenStream,
+    life: Option<&TokenStream>,
+) -> TokenStream {
     let core = &p.core;
-    let asyncness = &p.asyncness;
     match a {
-        Arg::I32 => quote! {#core::primitive::u32},
-        Arg::I64 => quote! {#core::primitive::u64},
+        Arg::I32 => match p.flags.signed {
+            false => quote! {#core::primitive::u32},
+            true => quote! {#core::primitive::i32},
+        },
+        Arg::I64 => match p.flags.signed {
+            false => quote! {#core::primitive::u64},
+            true => quote! {#core::primitive::i64},
+        },
         Arg::F32 => quote! {#core::primitive::f32},
         Arg::F64 => quote! {#core::primitive::f64},
         Arg::Resource {
@@ -93,12 +265,64 @@ pub fn arg(p: &Params, a: &Arg, root: [u8; 32]) -> TokenStream {
             take,
             ann,
         } => {
-            let x = match ty {
-                pit_core::ResTy::None => {
-                    return quote! {
-                        impl #core::marker::Sized + 'bound
-                    };
+            if let pit_core::ResTy::None = ty {
+                let life = life.iter();
+                let mut a = match (*take, p.flags.dyn_compatible) {
+                    (true, _) => quote! {
+                        AnyResource
+                    },
+                    (false, true) => quote! {
+                        &#(#life)* mut AnyResource
+                    },
+                    (false, false) => quote! {
+                        impl #core::ops::DerefMut<Target = AnyResource> + 'bound
+                    },
+                };
+                if *nullable {
+                    a = quote! {
+                        #core::option::Option<#a>
+                    }
+                }
+                return a;
+            }
+            if p.flags.dyn_compatible {
+                let x = match ty {
+                    pit_core::ResTy::Of(a) => {
+                        let x = name(p, *a);
+                        quote! {
+                            dyn #x<'bound,Error = #e> + 'bound
+                        }
+                    }
+                    pit_core::ResTy::This => {
+                        let x = name(p, root);
+                        quote! {
+                            dyn #x<'bound,Error = #e> + 'bound
+                        }
+                    }
+                    _ => {
+                        return quote! {
+                            #core::convert::Infallible
+                        };
+                    }
+                };
+                let alloc = alloc(p);
+                let life = life.iter();
+                let mut a = match *take {
+                    true => quote! {
+                        #alloc::boxed::Box<#x>
+                    },
+                    false => quote! {
+                        &#(#life)* mut (#x)
+                    },
+                };
+                if *nullable {
+                    a = quote! {
+                        #core::option::Option<#a>
+                    }
                 }
+                return a;
+            }
+            let x = match ty {
                 pit_core::ResTy::Of(a) => *a,
                 pit_core::ResTy::This => root,
                 _ => {
@@ -113,20 +337,9 @@ pub fn arg(p: &Params, a: &Arg, root: [u8; 32]) -> TokenStream {
                     }
                 }
             };
-            let x = hex::encode(&x);
-            let x = format_ident!(
-                "P{}{}{x}",
-                match p.flags.specialization {
-                    false => "",
-                    true => "S",
-                },
-                match asyncness.as_ref() {
-                    None => "",
-                    Some(_) => "async",
-                }
-            );
+            let x = name(p, x);
             let mut a = quote! {
-                impl #x<'bound,Error = Self::Error> + 'bound
+                impl #x<'bound,Error = #e> + 'bound
             };
             if !*take {
                 a = quote! {
@@ -154,6 +367,187 @@ pub fn arg(p: &Params, a: &Arg, root: [u8; 32]) -> TokenStream {
     }
 }
 
+/// Returns whether [`Params::core`] names `std`.
+fn is_std(p: &Params) -> bool {
+    p.core.segments.last().is_some_and(|a| a.ident == "std")
+}
+
+/// Generates the argument identifiers `arg0`, `arg1`, ... of a method.
+///
+/// These are used in generated implementations, where they cannot clash with other bindings.
+fn args(s: &Sig) -> Vec<Ident> {
+    (0..s.params.len())
+        .map(|a| format_ident!("arg{a}"))
+        .collect()
+}
+
+/// Returns the annotation `key` of the parameter at index `i` of `s`.
+///
+/// The annotation is taken from the parameter itself if it is a resource, and
+/// from a `p<i>.<key>` annotation on the method otherwise.
+fn param_ann<'a>(s: &'a Sig, i: usize, key: &str) -> Option<&'a str> {
+    let own = match &s.params[i] {
+        Arg::Resource { ann: a, .. } => ann(a, key),
+        _ => None,
+    };
+    own.or_else(|| ann(&s.ann, &format!("p{i}.{key}")))
+}
+
+/// Generates the parameter identifiers of a method in the trait definition.
+///
+/// Parameters are named by their `name` annotations (see [`param_ann`]), falling back
+/// to `arg0`, `arg1`, ... for unnamed parameters and invalid or duplicate names.
+fn param_names(s: &Sig) -> Vec<Ident> {
+    let mut names: Vec<Ident> = vec![];
+    for i in 0..s.params.len() {
+        let a = param_ann(s, i, "name")
+            .and_then(|a| syn::parse_str::<Ident>(a).ok())
+            .filter(|a| !names.contains(a))
+            .unwrap_or_else(|| format_ident!("arg{i}"));
+        names.push(a);
+    }
+    names
+}
+
+/// Generates `#[doc]` attributes from the `doc` annotations in `ann`.
+fn docs(ann: &[Attr]) -> TokenStream {
+    let docs = ann.iter().filter(|a| a.name == "doc").map(|a| &a.value);
+    quote! {
+        #(#[doc = #docs])*
+    }
+}
+
+/// Generates the `#[doc]` attributes of a method.
+///
+/// These are the method's `doc` annotations, followed by an `# Arguments` section
+/// listing the parameters with `doc` annotations (see [`param_ann`]).
+fn method_docs(s: &Sig) -> TokenStream {
+    let d = docs(&s.ann);
+    let names = param_names(s);
+    let params = (0..s.params.len())
+        .filter_map(|i| {
+            let doc = param_ann(s, i, "doc")?;
+            Some(format!("* `{}` - {doc}", names[i]))
+        })
+        .collect::<Vec<_>>();
+    if params.is_empty() {
+        return d;
+    }
+    quote! {
+        #d
+        #[doc = ""]
+        #[doc = "# Arguments"]
+        #[doc = ""]
+        #(#[doc = #params])*
+    }
+}
+
+/// Generates `.await` on `call` when generating async traits.
+fn awaited(p: &Params, call: TokenStream) -> TokenStream {
+    match p.asyncness {
+        None => call,
+        Some(_) => quote! {
+            #call.await
+        },
+    }
+}
+
+/// Returns whether async methods return boxed futures instead of being `async fn`s.
+///
+/// `async fn`s are neither dyn-compatible nor specializable.
+fn boxed(p: &Params) -> bool {
+    p.asyncness.is_some() && (p.flags.dyn_compatible || p.flags.specialization)
+}
+
+/// Generates a method in an implementation of the trait for the interface `root`.
+///
+/// `body` evaluates to the method's result and may use `.await` when generating async traits.
+fn method_impl(p: &Params, m: &str, s: &Sig, root: [u8; 32], body: TokenStream) -> TokenStream {
+    let f = format_ident!("{m}");
+    let sig = sig_with(p, s, root, &args(s));
+    match (&p.asyncness, boxed(p)) {
+        (None, _) => quote! {
+            fn #f #sig{
+                #body
+            }
+        },
+        (Some(asyncness), false) => quote! {
+            #asyncness fn #f #sig{
+                #body
+            }
+        },
+        (Some(_), true) => {
+            let alloc = alloc(p);
+            quote! {
+                fn #f #sig{
+                    #alloc::boxed::Box::pin(async move{
+                        #body
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Generates forwarding implementations of the trait for smart pointers.
+///
+/// See [`FeatureFlags::forwarding`].
+fn forwarding(p: &Params, i: &Interface) -> TokenStream {
+    let root = i.rid();
+    let core = &p.core;
+    let alloc = alloc(p);
+    let x = name(p, root);
+    let forward = |target: TokenStream| {
+        let methods = i.methods.iter().map(|(m, s)| {
+            let f = format_ident!("{m}");
+            let args = args(s);
+            let call = awaited(
+                p,
+                quote! {
+                    #target.#f(#(#args),*)
+                },
+            );
+            method_impl(p, m, s, root, call)
+        });
+        quote! {
+            type Error = T::Error;
+            #(#methods)*
+        }
+    };
+    let deref = forward(quote! {(**self)});
+    let mut a = quote! {
+        impl<'a,'bound,T: #x<'bound> + ?#core::marker::Sized> #x<'bound> for &'a mut T where 'a: 'bound{
+            #deref
+        }
+        impl<'bound,T: #x<'bound> + ?#core::marker::Sized> #x<'bound> for #alloc::boxed::Box<T>{
+            #deref
+        }
+    };
+    // Values returned through a guard may not borrow from it.
+    let guarded = i.methods.values().all(|s| {
+        s.rets.iter().all(|a| match a {
+            Arg::Resource { take, .. } => p.flags.dyn_compatible && *take,
+            _ => true,
+        })
+    });
+    if is_std(p) && guarded {
+        let cell = forward(quote! {#core::cell::RefCell::borrow_mut(self)});
+        let mutex = forward(quote! {
+            #core::sync::Mutex::lock(self).unwrap_or_else(#core::sync::PoisonError::into_inner)
+        });
+        a = quote! {
+            #a
+            impl<'bound,T: #x<'bound> + ?#core::marker::Sized> #x<'bound> for #core::rc::Rc<#core::cell::RefCell<T>>{
+                #cell
+            }
+            impl<'bound,T: #x<'bound> + ?#core::marker::Sized> #x<'bound> for #core::sync::Arc<#core::sync::Mutex<T>>{
+                #mutex
+            }
+        };
+    }
+    a
+}
+
 /// Generates a Rust method signature from a PIT method signature.
 ///
 /// Converts a [`pit_core::Sig`] into a [`TokenStream`] representing a Rust method
@@ -168,18 +562,39 @@ pub fn arg(p: &Params, a: &Arg, root: [u8; 32]) -> TokenStream {
 /// # Returns
 ///
 /// A `TokenStream` containing the method signature (parameters and return type).
+///
+/// Parameters are named by `name` annotations: a `[name=...]` annotation on a resource
+/// parameter, or a `[p0.name=...]`-style annotation on the method. Unnamed parameters
+/// are named `arg0`, `arg1`, ...
 pub fn sig(p: &Params, s: &Sig, root: [u8; 32]) -> TokenStream {
-    let params = s.params.iter().enumerate().map(|(a, b)| {
-        let a = format_ident!("arg{a}");
-        let b = arg(p, b, root);
+    sig_with(p, s, root, &param_names(s))
+}
+
+/// Generates a Rust method signature with the parameter identifiers `names`.
+fn sig_with(p: &Params, s: &Sig, root: [u8; 32], names: &[Ident]) -> TokenStream {
+    let params = s.params.iter().zip(names).enumerate().map(|(i, (b, a))| {
+        let b = arg(&at(p, s, &format!("p{i}")), b, root);
         quote! {
             #a : #b
         }
     });
-    let rets = s.rets.iter().map(|a| arg(p, a, root));
+    let rets = s
+        .rets
+        .iter()
+        .enumerate()
+        .map(|(i, a)| arg(&at(p, s, &format!("r{i}")), a, root));
     let core = &p.core;
+    let ret = quote! {
+        #core::result::Result<(#(#rets),*),Self::Error>
+    };
+    if boxed(p) {
+        let alloc = alloc(p);
+        return quote! {
+            <'life>(&'life mut self, #(#params),*) -> #core::pin::Pin<#alloc::boxed::Box<dyn #core::future::Future<Output = #ret> + 'life>> where 'bound: 'life
+        };
+    }
     quote! {
-        (&mut self, #(#params),*) -> #core::result::Result<(#(#rets),*),Self::Error>
+        (&mut self, #(#params),*) -> #ret
     }
 }
 
@@ -191,11 +606,22 @@ pub fn sig(p: &Params, s: &Sig, root: [u8; 32]) -> TokenStream {
 /// # Generated Code Structure
 ///
 /// The generated code includes:
-/// - An error struct named `P<interface_id>Error`
+/// - A public error enum named `P<interface_id>Error`, used by the specialization defaults
 /// - A trait named `P<interface_id>` with:
 ///   - An associated `Error` type
 ///   - Methods corresponding to the interface methods
-/// - Optionally, a default implementation using specialization
+/// - A struct named `P<interface_id>Meta` with the `RID`, `PIT` and `METHODS` consts
+///   describing the interface
+/// - Optionally, a `default impl` for every type using specialization (see [`FeatureFlags::specialization`])
+/// - Optionally, forwarding implementations for smart pointers (see [`FeatureFlags::forwarding`])
+///
+/// The trait and its methods are documented by `[doc=...]` annotations on the
+/// interface and methods, and by `doc` annotations on parameters (see [`sig`] for
+/// the annotation keys of parameters).
+///
+/// If the interface has a readable name (see [`readable_name`]), the trait is named
+/// after it and re-exported as `P<interface_id>`, so references from other
+/// interfaces still resolve.
 ///
 /// # Arguments
 ///
@@ -205,31 +631,40 @@ pub fn sig(p: &Params, s: &Sig, root: [u8; 32]) -> TokenStream {
 /// # Returns
 ///
 /// A `TokenStream` containing the complete trait definition and supporting types.
+///
+/// # Panics
+///
+/// Panics if a method name is not a valid identifier; [`try_interface`] reports
+/// these as [`GenError::InvalidName`] instead.
 pub fn interface(p: &Params, i: &Interface) -> TokenStream {
+    let p = &scoped(p, i);
     let root = i.rid();
-    let asyncness = &p.asyncness;
-    let x = hex::encode(&root);
-    let x = format_ident!(
-        "P{}{}{x}",
-        match p.flags.specialization {
-            false => "",
-            true => "S",
-        },
-        match asyncness.as_ref() {
-            None => "",
-            Some(_) => "async",
-        }
-    );
+    let asyncness = match boxed(p) {
+        false => &p.asyncness,
+        true => &None,
+    };
+    let x = name(p, root);
     let xe = format_ident!("{x}Error");
     let core = &p.core;
+    let (t, alias) = match readable_name(p, i) {
+        Some(t) => {
+            let alias = quote! {
+                pub use #t as #x;
+            };
+            (t, alias)
+        }
+        None => (x.clone(), quote! {}),
+    };
     let methods = i
         .methods
         .iter()
         .map(|(a, b)| {
             let asyncness = asyncness.iter();
             let a = format_ident!("{a}");
+            let d = method_docs(b);
             let b = sig(p, b, root);
             quote! {
+                #d
                 #(#asyncness)* fn  #a #b
             }
         })
@@ -237,38 +672,246 @@ pub fn interface(p: &Params, i: &Interface) -> TokenStream {
     let spec = match p.flags.specialization {
         false => quote! {},
         true => {
-            let method_impls = methods.iter().map(|a| {
-                quote! {
-                    #a {
-                        return #core::result::Result::Err(#xe{})
-                    }
-                }
+            // Methods returning `impl Trait` cannot be specialized.
+            let opaque = |s: &Sig| {
+                !p.flags.dyn_compatible
+                    && !s
+                        .rets
+                        .iter()
+                        .all(|a| matches!(a, Arg::I32 | Arg::I64 | Arg::F32 | Arg::F64))
+            };
+            let method_impls = i.methods.iter().filter(|(_, s)| !opaque(s)).map(|(m, s)| {
+                method_impl(
+                    p,
+                    m,
+                    s,
+                    root,
+                    quote! {
+                        #core::result::Result::Err(#core::convert::From::from(#xe::Unimplemented{method: #m}))
+                    },
+                )
             });
             quote! {
-                const _: () = {
-                    default impl<'bound,T: #core::marker::Sized + 'bound> #x<'bound> for T{
-                        type Error = #xe;
-                        #(#method_impls);*
-                    }
+                #[allow(unused_variables)]
+                default impl<'bound,T: #core::marker::Sized + 'bound> #x<'bound> for T{
+                    type Error = #xe;
+                    #(#method_impls)*
                 }
             }
         }
     };
+    let forwarding = match p.flags.forwarding {
+        false => quote! {},
+        true => forwarding(p, i),
+    };
+    let alloc = alloc(p);
+    let unimplemented = format!("`{{}}` is not implemented for {x}");
+    let meta = meta(p, i);
+    let docs = docs(&i.ann);
+    // The specialization defaults return `#xe`.
+    let error_bound = match p.flags.specialization {
+        false => quote! {},
+        true => quote! {+ #core::convert::From<#xe>},
+    };
     quote! {
-        #[derive(#core::Clone,#core::Copy,#core::Debug)]
-        struct #xe{}
+        /// Errors raised by implementations of the interface.
+        #[derive(#core::fmt::Debug)]
+        #[non_exhaustive]
+        pub enum #xe{
+            /// The method has no implementation.
+            Unimplemented{
+                /// The name of the method.
+                method: &'static #core::primitive::str,
+            },
+            /// A null resource was passed in a non-nullable position.
+            NullResource,
+            /// The transport carrying the call failed.
+            Transport(#alloc::boxed::Box<dyn #core::error::Error + #core::marker::Send + #core::marker::Sync>),
+            /// The callee trapped.
+            Trap,
+        }
         const _: () = {
             impl #core::fmt::Display for #xe{
-                fn fmt(&self, a: &mut #core::fmt::Formatter) -> #core::fmt::Result{
-                    #core::fmt::Result::Ok(())
+                fn fmt(&self, f: &mut #core::fmt::Formatter) -> #core::fmt::Result{
+                    match self{
+                        #xe::Unimplemented{method} => #core::write!(f, #unimplemented, method),
+                        #xe::NullResource => f.write_str("null resource in non-nullable position"),
+                        #xe::Transport(e) => #core::write!(f, "transport error: {}", e),
+                        #xe::Trap => f.write_str("trap"),
+                    }
+                }
+            }
+            impl #core::error::Error for #xe{
+                fn source(&self) -> #core::option::Option<&(dyn #core::error::Error + 'static)>{
+                    match self{
+                        #xe::Transport(e) => #core::option::Option::Some(&**e),
+                        _ => #core::option::Option::None,
+                    }
                 }
             }
-            impl #core::error::Error for #xe{}
         };
-        pub trait #x<'bound>: 'bound{
-            type Error: #core::error::Error;
-            #(#methods);*
+        #docs
+        pub trait #t<'bound>: 'bound{
+            /// The error returned by the methods.
+            type Error: #core::error::Error #error_bound;
+            #(#methods;)*
         }
+        #alias
+        #meta
         #spec
+        #forwarding
+    }
+}
+
+/// Generates the `P<interface_id>Meta` struct describing the interface.
+///
+/// The metadata lives on a separate struct, as associated consts would make
+/// dyn-compatible traits unusable as trait objects.
+fn meta(p: &Params, i: &Interface) -> TokenStream {
+    let core = &p.core;
+    let root = i.rid();
+    let x = name(p, root);
+    let m = format_ident!("{x}Meta");
+    let doc = format!("Metadata of the interface implemented through [`{x}`].");
+    let pit = i.to_string();
+    let methods = i.methods.iter().map(|(a, b)| {
+        let b = b.to_string();
+        quote! {
+            (#a, #b)
+        }
+    });
+    quote! {
+        #[doc = #doc]
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::marker::Copy, #core::default::Default)]
+        pub struct #m;
+        impl #m{
+            /// The resource ID of the interface.
+            pub const RID: [#core::primitive::u8; 32] = [#(#root),*];
+            /// The canonical PIT text of the interface.
+            pub const PIT: &'static #core::primitive::str = #pit;
+            /// The methods of the interface, as pairs of names and PIT signatures.
+            pub const METHODS: &'static [(&'static #core::primitive::str, &'static #core::primitive::str)] = &[#(#methods),*];
+        }
+    }
+}
+
+/// An error describing a PIT construct that cannot be represented in generated code.
+///
+/// Returned by [`try_arg`], [`try_sig`] and [`try_interface`]; the infallible functions
+/// instead emit `core::convert::Infallible` (or `impl Sized` with specialization).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum GenError {
+    /// An argument of an unsupported kind.
+    UnsupportedArg {
+        /// The method containing the argument, if known.
+        method: Option<String>,
+        /// The argument, in PIT syntax.
+        arg: String,
+    },
+    /// A resource of an unsupported type.
+    UnsupportedResource {
+        /// The method containing the resource, if known.
+        method: Option<String>,
+        /// The resource, in PIT syntax.
+        arg: String,
+    },
+    /// A method name or `name` annotation that is not a valid identifier.
+    InvalidName {
+        /// The name.
+        name: String,
+    },
+}
+
+impl GenError {
+    /// Sets the method the error occurred in.
+    fn in_method(mut self, m: &str) -> Self {
+        match &mut self {
+            GenError::UnsupportedArg { method, .. }
+            | GenError::UnsupportedResource { method, .. } => *method = Some(m.to_owned()),
+            GenError::InvalidName { .. } => {}
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for GenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (what, method, arg) = match self {
+            GenError::UnsupportedArg { method, arg } => ("argument", method, arg),
+            GenError::UnsupportedResource { method, arg } => ("resource type", method, arg),
+            GenError::InvalidName { name } => {
+                return write!(f, "invalid name `{name}`: not a Rust identifier");
+            }
+        };
+        write!(f, "unsupported {what} `{arg}`")?;
+        if let Some(m) = method {
+            write!(f, " in method `{m}`")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GenError {}
+
+/// Fallible version of [`arg`], failing on constructs it would not represent faithfully.
+pub fn try_arg(p: &Params, a: &Arg, root: [u8; 32]) -> Result<TokenStream, GenError> {
+    match a {
+        Arg::I32 | Arg::I64 | Arg::F32 | Arg::F64 => {}
+        Arg::Resource {
+            ty: pit_core::ResTy::None | pit_core::ResTy::Of(_) | pit_core::ResTy::This,
+            ..
+        } => {}
+        Arg::Resource { .. } => {
+            return Err(GenError::UnsupportedResource {
+                method: None,
+                arg: a.to_string(),
+            });
+        }
+        _ => {
+            return Err(GenError::UnsupportedArg {
+                method: None,
+                arg: a.to_string(),
+            });
+        }
+    }
+    Ok(arg(p, a, root))
+}
+
+/// Fallible version of [`sig`], failing on the first unsupported parameter or result.
+pub fn try_sig(p: &Params, s: &Sig, root: [u8; 32]) -> Result<TokenStream, GenError> {
+    for a in s.params.iter().chain(s.rets.iter()) {
+        try_arg(p, a, root)?;
+    }
+    Ok(sig(p, s, root))
+}
+
+/// Fallible version of [`interface`], failing on the first unsupported construct
+/// or invalid name.
+///
+/// The error records the method the construct occurred in.
+pub fn try_interface(p: &Params, i: &Interface) -> Result<TokenStream, GenError> {
+    check(p, i)?;
+    Ok(interface(p, i))
+}
+
+/// Checks that `i` contains no construct [`try_interface`] fails on.
+pub(crate) fn check(p: &Params, i: &Interface) -> Result<(), GenError> {
+    let valid = |name: &str| match syn::parse_str::<Ident>(name) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(GenError::InvalidName {
+            name: name.to_owned(),
+        }),
+    };
+    if !p.names.contains_key(&i.rid())
+        && let Some(a) = ann(&i.ann, "name")
+    {
+        valid(a)?;
+    }
+    let root = i.rid();
+    for (m, s) in i.methods.iter() {
+        valid(m)?;
+        try_sig(p, s, root).map_err(|e| e.in_method(m))?;
     }
+    Ok(())
 }
@@ -0,0 +1,319 @@
+//! Mock implementations of interface traits for unit tests.
+//!
+//! The generated mocks implement the interface traits generated with the same
+//! [`Params`], so they can stand in for any resource of the interface. Calls are
+//! answered by scripted closures in order, then by an optional fallback, and
+//! otherwise fail with the interface's `Unimplemented` error. Every call is
+//! recorded, with resource arguments recorded as `()`.
+//!
+//! Unless [`FeatureFlags::dyn_compatible`] is set, resource arguments cannot be
+//! named by the scripted closures: they are passed as `()`, and resources are
+//! returned as the mocks of their interfaces, boxed for borrowed resources.
+//!
+//! [`FeatureFlags::dyn_compatible`]: crate::FeatureFlags::dyn_compatible
+
+use std::collections::BTreeSet;
+
+use pit_core::{Arg, Interface};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{
+    Params, alloc, arg_in, args, at, guest::modifiers, method_impl, name, readable_name, scoped,
+};
+
+/// Generates the identifier of the mock for the interface with the given resource ID.
+///
+/// The name is `Mock` followed by the trait name (see [`name`]); mocks of interfaces
+/// with readable names are re-exported under it.
+pub fn mock_name(p: &Params, rid: [u8; 32]) -> Ident {
+    format_ident!("Mock{}", name(p, rid))
+}
+
+/// Generates the type of a value of type `a` taken or returned by the closures of a mock,
+/// with the error type `e`.
+fn handled(
+    p: &Params,
+    a: &Arg,
+    root: [u8; 32],
+    e: &TokenStream,
+    life: Option<&TokenStream>,
+    ret: bool,
+) -> TokenStream {
+    let core = &p.core;
+    let Arg::Resource { ty, .. } = a else {
+        return arg_in(p, a, root, e, life);
+    };
+    if p.flags.dyn_compatible {
+        return arg_in(p, a, root, e, life);
+    }
+    if !ret {
+        return quote! {()};
+    }
+    let alloc = alloc(p);
+    let (nullable, take) = modifiers(a);
+    let mut t = match ty {
+        pit_core::ResTy::None => quote! {AnyResource},
+        pit_core::ResTy::Of(r) => {
+            let m = mock_name(p, *r);
+            quote! {#m<'bound, #e>}
+        }
+        pit_core::ResTy::This => {
+            let m = mock_name(p, root);
+            quote! {#m<'bound, #e>}
+        }
+        _ => return arg_in(p, a, root, e, life),
+    };
+    if !take {
+        t = quote! {#alloc::boxed::Box<#t>};
+    }
+    if nullable {
+        t = quote! {#core::option::Option<#t>};
+    }
+    t
+}
+
+/// Generates a mock implementation of the trait for a PIT interface.
+///
+/// For an interface named `X` (see [`readable_name`]), this produces:
+/// - `MockX<'bound, E = P<interface_id>Error>`, implementing the trait with `Error = E`,
+///   re-exported as `Mock<trait>` (see [`mock_name`])
+/// - `MockXCall`, with one variant per method recording its primitive arguments
+///
+/// `MockX::new()` creates a mock failing with `P<interface_id>Error::Unimplemented`;
+/// `MockX::with_unimplemented(f)` creates a mock with another error type, built by `f`
+/// from the name of the method. Every interface referenced as a resource must also
+/// have its mock generated in the same module, unless
+/// [`FeatureFlags::dyn_compatible`](crate::FeatureFlags::dyn_compatible) is set.
+///
+/// With [`FeatureFlags::specialization`](crate::FeatureFlags::specialization), the error
+/// type must also convert from the errors of every interface passed or returned, so
+/// such mocks are created with `with_unimplemented`. Unless `dyn_compatible` is set,
+/// the returned mocks need the conversions of their own interfaces too, which are
+/// only required one level deep: an interface referencing another that references a
+/// third cannot be mocked in that mode.
+///
+/// For each method `m`, `MockX` provides:
+/// - `expect_m(f)` - Answers the next unanswered call with `f`
+/// - `return_m(r)` - Answers the next unanswered call with `r`
+/// - `on_m(f)` - Answers calls with `f` once the scripted answers run out
+///
+/// Recorded calls are available from `calls()`, and `assert_called(method, times)`
+/// checks how often a method was called.
+///
+/// # Arguments
+///
+/// * `p` - Code generation parameters, as passed to [`crate::interface`]
+/// * `i` - The PIT interface to mock
+///
+/// # Returns
+///
+/// A `TokenStream` containing the mock and call record types.
+pub fn mock(p: &Params, i: &Interface) -> TokenStream {
+    let p = &scoped(p, i);
+    let root = i.rid();
+    let core = &p.core;
+    let alloc = alloc(p);
+    let x = name(p, root);
+    let xe = format_ident!("{x}Error");
+    let e = quote! {E};
+    let life = quote! {'bound};
+    let (mock, alias) = match readable_name(p, i) {
+        Some(t) => {
+            let m = format_ident!("Mock{t}");
+            let n = mock_name(p, root);
+            (
+                m.clone(),
+                quote! {
+                    pub use #m as #n;
+                },
+            )
+        }
+        None => (mock_name(p, root), quote! {}),
+    };
+    let call = format_ident!("{mock}Call");
+    let mut fields = vec![];
+    let mut inits = vec![];
+    let mut scripts = vec![];
+    let mut variants = vec![];
+    let mut names = vec![];
+    let mut impls = vec![];
+    for (m, s) in i.methods.iter() {
+        let f = format_ident!("{m}");
+        let expect = format_ident!("expect_{m}");
+        let ret = format_ident!("return_{m}");
+        let on = format_ident!("on_{m}");
+        let fallback = format_ident!("fallback_{m}");
+        let params = s
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, a)| handled(&at(p, s, &format!("p{i}")), a, root, &e, None, false))
+            .collect::<Vec<_>>();
+        let rets = s
+            .rets
+            .iter()
+            .enumerate()
+            .map(|(i, a)| handled(&at(p, s, &format!("r{i}")), a, root, &e, Some(&life), true));
+        let result = quote! {
+            #core::result::Result<(#(#rets),*),E>
+        };
+        let handler = quote! {
+            #alloc::boxed::Box<dyn #core::ops::FnMut(#(#params),*) -> #result + 'bound>
+        };
+        fields.push(quote! {
+            #expect: #alloc::collections::VecDeque<#handler>,
+            #fallback: #core::option::Option<#handler>
+        });
+        inits.push(quote! {
+            #expect: #alloc::collections::VecDeque::new(),
+            #fallback: #core::option::Option::None
+        });
+        let ignored = s.params.iter().map(|_| quote! {_});
+        let expect_doc = format!("Answers the next unanswered call to `{m}` with `f`.");
+        let ret_doc = format!("Answers the next unanswered call to `{m}` with `r`.");
+        let on_doc = format!("Answers calls to `{m}` with `f` once the scripted answers run out.");
+        scripts.push(quote! {
+            #[doc = #expect_doc]
+            pub fn #expect(&mut self, f: impl #core::ops::FnMut(#(#params),*) -> #result + 'bound) -> &mut Self{
+                self.#expect.push_back(#alloc::boxed::Box::new(f));
+                self
+            }
+            #[doc = #ret_doc]
+            pub fn #ret(&mut self, r: #result) -> &mut Self{
+                let mut r = #core::option::Option::Some(r);
+                self.#expect(move |#(#ignored),*| r.take().expect("scripted result already returned"))
+            }
+            #[doc = #on_doc]
+            pub fn #on(&mut self, f: impl #core::ops::FnMut(#(#params),*) -> #result + 'bound) -> &mut Self{
+                self.#fallback = #core::option::Option::Some(#alloc::boxed::Box::new(f));
+                self
+            }
+        });
+        let recorded = s.params.iter().enumerate().map(|(i, a)| match a {
+            Arg::Resource { .. } => quote! {()},
+            _ => arg_in(&at(p, s, &format!("p{i}")), a, root, &e, None),
+        });
+        variants.push(quote! {
+            #f(#(#recorded),*)
+        });
+        names.push(quote! {
+            #call::#f(..) => #m
+        });
+        let args = args(s);
+        let captures = s.params.iter().zip(args.iter()).map(|(a, b)| match a {
+            Arg::Resource { .. } => quote! {()},
+            _ => quote! {#b},
+        });
+        let passed = s
+            .params
+            .iter()
+            .zip(args.iter())
+            .map(|(a, b)| match (a, p.flags.dyn_compatible) {
+                (Arg::Resource { .. }, false) => quote! {
+                    {
+                        let _ = #b;
+                    }
+                },
+                _ => quote! {#b},
+            })
+            .collect::<Vec<_>>();
+        let body = quote! {
+            self.calls.push(#call::#f(#(#captures),*));
+            match self.#expect.pop_front(){
+                #core::option::Option::Some(mut f) => f(#(#passed),*),
+                #core::option::Option::None => match &mut self.#fallback{
+                    #core::option::Option::Some(f) => f(#(#passed),*),
+                    #core::option::Option::None => #core::result::Result::Err((self.unimplemented)(#m)),
+                },
+            }
+        };
+        impls.push(method_impl(p, m, s, root, body));
+    }
+    let mock_doc = format!("A mock implementation of [`{x}`].");
+    let call_doc = format!("A call recorded by [`{mock}`].");
+    // The specialization defaults require the error type to convert from `#xe`, and
+    // the mocks of the referenced interfaces from their own error types.
+    let error_bound = match p.flags.specialization {
+        false => quote! {},
+        true => {
+            let referenced = i
+                .methods
+                .values()
+                .flat_map(|s| s.params.iter().chain(s.rets.iter()))
+                .filter_map(|a| match a {
+                    Arg::Resource {
+                        ty: pit_core::ResTy::Of(r),
+                        ..
+                    } if *r != root => Some(*r),
+                    _ => None,
+                })
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|r| format_ident!("{}Error", name(p, r)));
+            quote! {+ #core::convert::From<#xe> #(+ #core::convert::From<#referenced>)*}
+        }
+    };
+    quote! {
+        #[doc = #mock_doc]
+        pub struct #mock<'bound, E = #xe>{
+            #(#fields,)*
+            calls: #alloc::vec::Vec<#call>,
+            unimplemented: fn(&'static #core::primitive::str) -> E,
+        }
+        #alias
+        #[doc = #call_doc]
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::cmp::PartialEq)]
+        #[allow(non_camel_case_types)]
+        pub enum #call{
+            #(#variants),*
+        }
+        const _: () = {
+            impl #call{
+                /// Returns the name of the called method.
+                pub fn method(&self) -> &'static #core::primitive::str{
+                    match *self{
+                        #(#names),*
+                    }
+                }
+            }
+            impl<'bound> #core::default::Default for #mock<'bound>{
+                fn default() -> Self{
+                    Self::new()
+                }
+            }
+            impl<'bound> #mock<'bound>{
+                /// Creates a mock with no scripted answers.
+                pub fn new() -> Self{
+                    Self::with_unimplemented(|method| #xe::Unimplemented{method})
+                }
+            }
+            impl<'bound, E: 'bound> #mock<'bound, E>{
+                /// Creates a mock with no scripted answers, whose unanswered calls fail
+                /// with the error returned by `unimplemented` for the name of the method.
+                pub fn with_unimplemented(unimplemented: fn(&'static #core::primitive::str) -> E) -> Self{
+                    Self{
+                        #(#inits,)*
+                        calls: #alloc::vec::Vec::new(),
+                        unimplemented,
+                    }
+                }
+                #(#scripts)*
+                /// Returns the calls made so far, in order.
+                pub fn calls(&self) -> &[#call]{
+                    &self.calls
+                }
+                /// Asserts that `method` was called exactly `times` times.
+                #[track_caller]
+                pub fn assert_called(&self, method: &#core::primitive::str, times: #core::primitive::usize){
+                    let n = self.calls.iter().filter(|a| a.method() == method).count();
+                    #core::assert_eq!(n, times, "expected `{}` to be called {} times, but it was called {} times", method, times, n);
+                }
+            }
+            impl<'bound, E: #core::error::Error #error_bound + 'bound> #x<'bound> for #mock<'bound, E>{
+                type Error = E;
+                #(#impls)*
+            }
+        };
+    }
+}
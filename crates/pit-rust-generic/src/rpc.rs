@@ -0,0 +1,529 @@
+//! Binary RPC for calling interfaces across process boundaries.
+//!
+//! The generated code always targets the dyn-compatible, synchronous form of the
+//! interface traits (see [`FeatureFlags::dyn_compatible`]) with `'bound = 'static`.
+//! Each side of a connection is a `Peer<T, E>`, serving its objects to the other side
+//! through the transport `T`; `E` is the error type of every object on both sides, and
+//! must implement `From<RpcError>`. Remote objects are reached through `Client<T, E>`,
+//! which implements the traits of every interface generated with [`rpc`].
+//!
+//! ## Frames
+//!
+//! A request is the handle of the object, the index of the method in the interface
+//! (sorted by name), then the arguments. A response is a status byte, then either the
+//! results (`0`) or a UTF-8 error message (`1`). Integers are little-endian: `I32` and
+//! `F32` take 4 bytes, `I64` and `F64` 8 bytes, and resources are passed as `u32` handles
+//! in the objects of the sending side, `0` denoting a null resource.
+//!
+//! Owned resources stay served by their sender until the receiving `Client` is dropped,
+//! which sends the method index `u32::MAX`. Borrowed arguments are only served for the
+//! duration of the call. Borrowed results and untyped resources cannot cross the
+//! boundary: their methods fail with `RpcError::Unsupported`.
+//!
+//! The generated code requires `alloc`, and the `HandleTable` type of
+//! [`crate::rt::handle_table`] in scope.
+
+use pit_core::{Arg, Interface, Sig};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{
+    FeatureFlags, Params, alloc, arg, args, at,
+    guest::{modifiers, tuple},
+    method_impl, name, scoped,
+};
+
+/// Returns the parameters used for RPC code generation.
+fn params(p: &Params) -> Params {
+    Params {
+        flags: FeatureFlags {
+            dyn_compatible: true,
+            signed: p.flags.signed,
+            ..Default::default()
+        },
+        asyncness: None,
+        ..p.clone()
+    }
+}
+
+/// Returns whether the values of `s` can cross an RPC boundary.
+fn supported(s: &Sig) -> bool {
+    let typed = |ty: &pit_core::ResTy| matches!(ty, pit_core::ResTy::Of(_) | pit_core::ResTy::This);
+    s.params.iter().all(|a| match a {
+        Arg::Resource { ty, .. } => typed(ty),
+        _ => true,
+    }) && s.rets.iter().all(|a| match a {
+        Arg::Resource { ty, take, .. } => *take && typed(ty),
+        _ => true,
+    })
+}
+
+/// Generates the shared RPC types.
+///
+/// This generates:
+/// - `RpcError`, the errors raised by the RPC machinery
+/// - `Transport`, carrying request frames to the other side of a connection
+/// - `Dispatch<T, E>`, implemented by the objects a peer can serve
+/// - `Peer<T, E>`, one side of a connection, serving its objects through `serve`
+/// - `Client<T, E>`, a handle to an object served by the other side
+/// - `Loopback<E>`, a transport connecting two peers in the same thread, for testing
+///
+/// This should be generated once per module, alongside the [`rpc`] implementations.
+pub fn runtime(p: &Params) -> TokenStream {
+    let core = &p.core;
+    let alloc = alloc(p);
+    quote! {
+        /// An error raised by the RPC machinery.
+        #[derive(#core::fmt::Debug)]
+        #[non_exhaustive]
+        pub enum RpcError{
+            /// The transport failed.
+            Transport(#alloc::boxed::Box<dyn #core::error::Error + #core::marker::Send + #core::marker::Sync>),
+            /// The remote object failed, with the given message.
+            Remote(#alloc::string::String),
+            /// A frame could not be decoded.
+            Malformed,
+            /// A borrowed result or an untyped resource cannot cross the boundary.
+            Unsupported,
+        }
+        /// Carries request frames to the other side of a connection.
+        pub trait Transport{
+            /// Sends `frame` to the other side, which passes it to [`Peer::serve`], and returns the response.
+            fn call(&self, frame: #alloc::vec::Vec<#core::primitive::u8>) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, RpcError>;
+        }
+        /// Implemented by the objects a [`Peer`] can serve.
+        pub trait Dispatch<T, E>{
+            /// Calls the method with index `method`, decoding its arguments from `args`
+            /// and returning its encoded results.
+            fn dispatch(&mut self, peer: &#alloc::rc::Rc<Peer<T, E>>, method: #core::primitive::u32, args: &mut Reader) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, E>;
+        }
+        /// One side of a connection: the objects served to the other side, and the transport to it.
+        pub struct Peer<T, E>{
+            transport: T,
+            objects: #core::cell::RefCell<HandleTable<#alloc::rc::Rc<#core::cell::RefCell<#alloc::boxed::Box<dyn Dispatch<T, E>>>>>>,
+        }
+        /// A handle to an object served by the other side of a connection.
+        pub struct Client<T: Transport, E>{
+            peer: #alloc::rc::Rc<Peer<T, E>>,
+            handle: #core::primitive::u32,
+            owned: #core::primitive::bool,
+        }
+        /// A transport to a peer in the same thread, for testing.
+        pub struct Loopback<E: 'static>(#core::cell::RefCell<#alloc::rc::Weak<Peer<Loopback<E>, E>>>);
+        /// A reader over the arguments or results in a frame.
+        #[doc(hidden)]
+        pub struct Reader<'a>(pub &'a [#core::primitive::u8]);
+        /// A borrowed object served for the duration of a call.
+        #[doc(hidden)]
+        pub struct Lent<X: ?#core::marker::Sized>(*mut X);
+        const _: () = {
+            impl #core::fmt::Display for RpcError{
+                fn fmt(&self, f: &mut #core::fmt::Formatter) -> #core::fmt::Result{
+                    match self{
+                        RpcError::Transport(e) => #core::write!(f, "transport error: {}", e),
+                        RpcError::Remote(e) => f.write_str(e),
+                        RpcError::Malformed => f.write_str("malformed RPC frame"),
+                        RpcError::Unsupported => f.write_str("value cannot cross an RPC boundary"),
+                    }
+                }
+            }
+            impl #core::error::Error for RpcError{
+                fn source(&self) -> #core::option::Option<&(dyn #core::error::Error + 'static)>{
+                    match self{
+                        RpcError::Transport(e) => #core::option::Option::Some(&**e),
+                        _ => #core::option::Option::None,
+                    }
+                }
+            }
+            impl<'a> Reader<'a>{
+                /// Reads the next `N` bytes.
+                pub fn take<const N: #core::primitive::usize>(&mut self) -> #core::result::Result<[#core::primitive::u8; N], RpcError>{
+                    let (a, rest) = self.0.split_first_chunk::<N>().ok_or(RpcError::Malformed)?;
+                    self.0 = rest;
+                    #core::result::Result::Ok(*a)
+                }
+            }
+            impl<T, E: #core::fmt::Display> Peer<T, E>{
+                /// Creates a peer serving no objects yet.
+                pub fn new(transport: T) -> #alloc::rc::Rc<Self>{
+                    #alloc::rc::Rc::new(Self{
+                        transport,
+                        objects: #core::cell::RefCell::new(HandleTable::new()),
+                    })
+                }
+                /// Returns the transport to the other side.
+                pub fn transport(&self) -> &T{
+                    &self.transport
+                }
+                /// Serves `object` to the other side, returning an owned handle to it.
+                ///
+                /// The other side reaches the object with [`Client::new`].
+                pub fn export(&self, object: #alloc::boxed::Box<dyn Dispatch<T, E>>) -> #core::primitive::u32{
+                    self.objects.borrow_mut().insert(#alloc::rc::Rc::new(#core::cell::RefCell::new(object)))
+                }
+                /// Stops serving the object of `handle`, dropping it.
+                pub fn revoke(&self, handle: #core::primitive::u32) -> #core::result::Result<(), HandleError>{
+                    // The object may own clients, whose drop calls back into this peer.
+                    let object = self.objects.borrow_mut().take(handle)?;
+                    #core::mem::drop(object);
+                    #core::result::Result::Ok(())
+                }
+                /// Handles a request frame from the other side, returning the response.
+                pub fn serve(self: &#alloc::rc::Rc<Self>, frame: &[#core::primitive::u8]) -> #alloc::vec::Vec<#core::primitive::u8>{
+                    let r = Self::call(self, &mut Reader(frame));
+                    let (status, body) = match r{
+                        #core::result::Result::Ok(a) => (0, a),
+                        #core::result::Result::Err(e) => (1, #alloc::string::String::into_bytes(e)),
+                    };
+                    let mut out = #alloc::vec::Vec::with_capacity(body.len() + 1);
+                    out.push(status);
+                    out.extend_from_slice(&body);
+                    out
+                }
+                fn call(self: &#alloc::rc::Rc<Self>, frame: &mut Reader) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, #alloc::string::String>{
+                    let error = |e: &dyn #core::fmt::Display| #alloc::string::ToString::to_string(e);
+                    let handle = #core::primitive::u32::from_le_bytes(frame.take().map_err(|e| error(&e))?);
+                    let method = #core::primitive::u32::from_le_bytes(frame.take().map_err(|e| error(&e))?);
+                    if method == #core::primitive::u32::MAX{
+                        self.revoke(handle).map_err(|e| error(&e))?;
+                        return #core::result::Result::Ok(#alloc::vec::Vec::new());
+                    }
+                    let object = self.objects.borrow().borrow(handle).map(#alloc::rc::Rc::clone).map_err(|e| error(&e))?;
+                    let mut object = object.try_borrow_mut().map_err(|_| error(&"reentrant call on a PIT object"))?;
+                    object.dispatch(self, method, frame).map_err(|e| error(&e))
+                }
+            }
+            impl<T: Transport, E> Client<T, E>{
+                /// Wraps an owned handle to an object served by the other side of `peer`.
+                pub fn new(peer: #alloc::rc::Rc<Peer<T, E>>, handle: #core::primitive::u32) -> Self{
+                    Self{
+                        peer,
+                        handle,
+                        owned: true,
+                    }
+                }
+                /// Wraps a borrowed handle, which is not released when dropped.
+                #[doc(hidden)]
+                pub fn lent(peer: #alloc::rc::Rc<Peer<T, E>>, handle: #core::primitive::u32) -> Self{
+                    Self{
+                        peer,
+                        handle,
+                        owned: false,
+                    }
+                }
+                /// Returns the handle of the object.
+                pub fn handle(&self) -> #core::primitive::u32{
+                    self.handle
+                }
+                /// Returns the peer of the connection.
+                pub fn peer(&self) -> &#alloc::rc::Rc<Peer<T, E>>{
+                    &self.peer
+                }
+                fn send(&self, method: #core::primitive::u32, args: &[#core::primitive::u8]) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, RpcError>{
+                    let mut frame = #alloc::vec::Vec::with_capacity(args.len() + 8);
+                    frame.extend_from_slice(&self.handle.to_le_bytes());
+                    frame.extend_from_slice(&method.to_le_bytes());
+                    frame.extend_from_slice(args);
+                    self.peer.transport.call(frame)
+                }
+                /// Calls the method with index `method`, returning the encoded results.
+                #[doc(hidden)]
+                pub fn call(&self, method: #core::primitive::u32, args: &[#core::primitive::u8]) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, RpcError>{
+                    let r = Self::send(self, method, args)?;
+                    match r.split_first(){
+                        #core::option::Option::Some((0, rets)) => #core::result::Result::Ok(rets.into()),
+                        #core::option::Option::Some((1, e)) => #core::result::Result::Err(RpcError::Remote(#alloc::string::String::from_utf8_lossy(e).into_owned())),
+                        _ => #core::result::Result::Err(RpcError::Malformed),
+                    }
+                }
+            }
+            impl<T: Transport, E> #core::ops::Drop for Client<T, E>{
+                fn drop(&mut self){
+                    if self.owned{
+                        // Failures only leak the object on the other side.
+                        let _ = Client::send(self, #core::primitive::u32::MAX, &[]);
+                    }
+                }
+            }
+            impl<X: ?#core::marker::Sized> Lent<X>{
+                /// Wraps a borrowed object, to be served until the call borrowing it returns.
+                ///
+                /// # Safety
+                ///
+                /// `object` must stay valid and unaliased until the `Lent` is dropped, which
+                /// requires revoking its handle before the borrow ends.
+                pub unsafe fn new(object: *mut X) -> Self{
+                    Self(object)
+                }
+            }
+            impl<E: #core::fmt::Display + 'static> Loopback<E>{
+                /// Creates two peers connected to each other.
+                pub fn pair() -> (#alloc::rc::Rc<Peer<Self, E>>, #alloc::rc::Rc<Peer<Self, E>>){
+                    let a = Peer::new(Loopback(#core::cell::RefCell::new(#alloc::rc::Weak::new())));
+                    let b = Peer::new(Loopback(#core::cell::RefCell::new(#alloc::rc::Rc::downgrade(&a))));
+                    *a.transport.0.borrow_mut() = #alloc::rc::Rc::downgrade(&b);
+                    (a, b)
+                }
+            }
+            impl<E: #core::fmt::Display + 'static> Transport for Loopback<E>{
+                fn call(&self, frame: #alloc::vec::Vec<#core::primitive::u8>) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, RpcError>{
+                    let peer = self.0.borrow().upgrade();
+                    match peer{
+                        #core::option::Option::Some(a) => #core::result::Result::Ok(a.serve(&frame)),
+                        #core::option::Option::None => #core::result::Result::Err(RpcError::Transport("peer was dropped".into())),
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// Generates the RPC implementations for a PIT interface.
+///
+/// This implements `Dispatch` for boxed trait objects of the interface, so a peer can
+/// serve them, and the trait for `Client`; see [`runtime`]. Every interface referenced
+/// as a resource must also have its implementations generated in the same module.
+///
+/// # Arguments
+///
+/// * `p` - Code generation parameters; only [`Params::core`] and [`FeatureFlags::signed`] are used
+/// * `i` - The PIT interface to call remotely
+///
+/// # Returns
+///
+/// A `TokenStream` containing the implementations.
+pub fn rpc(p: &Params, i: &Interface) -> TokenStream {
+    let p = &scoped(&params(p), i);
+    let root = i.rid();
+    let core = &p.core;
+    let alloc = alloc(p);
+    let x = name(p, root);
+    let bounds = quote! {
+        T: Transport + 'static, E: #core::error::Error + #core::convert::From<RpcError> + 'static
+    };
+    let obj = |r: [u8; 32]| {
+        let y = name(p, r);
+        quote! {
+            dyn #y<'static,Error = E> + 'static
+        }
+    };
+    let rid = |a: &Arg| match a {
+        Arg::Resource {
+            ty: pit_core::ResTy::Of(r),
+            ..
+        } => *r,
+        _ => root,
+    };
+    // The size in bytes of primitive values.
+    let size = |a: &Arg| match a {
+        Arg::I32 | Arg::F32 => Some(4usize),
+        Arg::I64 | Arg::F64 => Some(8),
+        _ => None,
+    };
+    let unsupported = quote! {
+        #core::result::Result::Err(#core::convert::From::from(RpcError::Unsupported))
+    };
+    let mut arms = vec![];
+    let mut methods = vec![];
+    for (index, (m, s)) in i.methods.iter().enumerate() {
+        let index = index as u32;
+        let f = format_ident!("{m}");
+        let args = args(s);
+        if !supported(s) {
+            arms.push(quote! {
+                #index => #unsupported,
+            });
+            methods.push(method_impl(
+                p,
+                m,
+                s,
+                root,
+                quote! {
+                    #(let _ = #args;)*
+                    #unsupported
+                },
+            ));
+            continue;
+        }
+        let rets = (0..s.rets.len())
+            .map(|a| format_ident!("r{a}"))
+            .collect::<Vec<_>>();
+        // Decodes the value `v` of type `a` from `frame`, wrapping resources in clients of `peer`.
+        let decode = |a: &Arg, key: String, v: &Ident, frame: TokenStream, peer: TokenStream| {
+            if let Some(n) = size(a) {
+                let ty = arg(&at(p, s, &key), a, root);
+                return quote! {
+                    let #v = #ty::from_le_bytes(#frame.take::<#n>()?);
+                };
+            }
+            let Arg::Resource { nullable, take, .. } = a else {
+                return quote! {};
+            };
+            let y = obj(rid(a));
+            let bind = match take {
+                true => quote! {
+                    let #v = match #core::primitive::u32::from_le_bytes(#frame.take()?){
+                        0 => #core::option::Option::None,
+                        h => #core::option::Option::Some(#alloc::boxed::Box::new(Client::new(#core::clone::Clone::clone(#peer), h)) as #alloc::boxed::Box<#y>),
+                    };
+                },
+                false => quote! {
+                    let mut #v = match #core::primitive::u32::from_le_bytes(#frame.take()?){
+                        0 => #core::option::Option::None,
+                        h => #core::option::Option::Some(Client::lent(#core::clone::Clone::clone(#peer), h)),
+                    };
+                    let #v = #v.as_mut().map(|a| a as &mut (#y));
+                },
+            };
+            match nullable {
+                true => bind,
+                false => quote! {
+                    #bind
+                    let #v = #v.ok_or(RpcError::Malformed)?;
+                },
+            }
+        };
+        // Encodes the value `v` of type `a` into `out`, serving resources from `peer`.
+        let encode = |a: &Arg, v: &Ident, peer: TokenStream| {
+            if size(a).is_some() {
+                return quote! {
+                    out.extend_from_slice(&#v.to_le_bytes());
+                };
+            }
+            let Arg::Resource { nullable, take, .. } = a else {
+                return quote! {};
+            };
+            let y = obj(rid(a));
+            let served = |a: TokenStream| match take {
+                true => quote! {
+                    #peer.export(#alloc::boxed::Box::new(#a))
+                },
+                false => quote! {
+                    {
+                        // SAFETY: the handle is revoked before the call returns.
+                        let h = #peer.export(#alloc::boxed::Box::new(unsafe{ Lent::new(#a as *mut (#y)) }));
+                        lent.push(h);
+                        h
+                    }
+                },
+            };
+            let h = match nullable {
+                true => {
+                    let h = served(quote! {a});
+                    quote! {
+                        #v.map_or(0, |a| #h)
+                    }
+                }
+                false => served(quote! {#v}),
+            };
+            quote! {
+                out.extend_from_slice(&#h.to_le_bytes());
+            }
+        };
+        let decoded = s
+            .params
+            .iter()
+            .zip(args.iter())
+            .enumerate()
+            .map(|(k, (a, v))| decode(a, format!("p{k}"), v, quote! {args}, quote! {peer}));
+        let encoded = s
+            .rets
+            .iter()
+            .zip(rets.iter())
+            .map(|(a, v)| encode(a, v, quote! {peer}));
+        let pat = tuple(rets.iter().map(|a| quote! {#a}));
+        let out = match s.rets.is_empty() {
+            true => quote! {out},
+            false => quote! {mut out},
+        };
+        arms.push(quote! {
+            #index => {
+                #(#decoded)*
+                let #pat = this.#f(#(#args),*)?;
+                let #out = #alloc::vec::Vec::new();
+                #(#encoded)*
+                #core::result::Result::Ok(out)
+            }
+        });
+        let out = match s.params.is_empty() {
+            true => quote! {out},
+            false => quote! {mut out},
+        };
+        let (lent, revoke) = match s.params.iter().any(|a| !modifiers(a).1) {
+            true => (
+                quote! {
+                    let mut lent = #alloc::vec::Vec::new();
+                },
+                quote! {
+                    for h in lent{
+                        let _ = self.peer.revoke(h);
+                    }
+                },
+            ),
+            false => (quote! {}, quote! {}),
+        };
+        let read = match s.rets.is_empty() {
+            true => quote! {
+                r?;
+            },
+            false => quote! {
+                let r = r?;
+                let mut rets = Reader(&r);
+            },
+        };
+        let encoded = s
+            .params
+            .iter()
+            .zip(args.iter())
+            .map(|(a, v)| encode(a, v, quote! {self.peer}));
+        let decoded = s
+            .rets
+            .iter()
+            .zip(rets.iter())
+            .enumerate()
+            .map(|(k, (a, v))| decode(a, format!("r{k}"), v, quote! {rets}, quote! {&self.peer}));
+        methods.push(method_impl(
+            p,
+            m,
+            s,
+            root,
+            quote! {
+                let #out = #alloc::vec::Vec::new();
+                #lent
+                #(#encoded)*
+                let r = Client::call(self, #index, &out);
+                #revoke
+                #read
+                #(#decoded)*
+                #core::result::Result::Ok(#pat)
+            },
+        ));
+    }
+    let this = obj(root);
+    quote! {
+        const _: () = {
+            fn dispatch<#bounds>(this: &mut (#this), peer: &#alloc::rc::Rc<Peer<T, E>>, method: #core::primitive::u32, args: &mut Reader) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, E>{
+                match method{
+                    #(#arms)*
+                    _ => #core::result::Result::Err(#core::convert::From::from(RpcError::Malformed)),
+                }
+            }
+            impl<#bounds> Dispatch<T, E> for #alloc::boxed::Box<#this>{
+                fn dispatch(&mut self, peer: &#alloc::rc::Rc<Peer<T, E>>, method: #core::primitive::u32, args: &mut Reader) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, E>{
+                    dispatch(&mut **self, peer, method, args)
+                }
+            }
+            impl<#bounds> Dispatch<T, E> for Lent<#this>{
+                fn dispatch(&mut self, peer: &#alloc::rc::Rc<Peer<T, E>>, method: #core::primitive::u32, args: &mut Reader) -> #core::result::Result<#alloc::vec::Vec<#core::primitive::u8>, E>{
+                    // SAFETY: the object is only served while its caller waits for the call to return,
+                    // as required by `Lent::new`.
+                    dispatch(unsafe{ &mut *self.0 }, peer, method, args)
+                }
+            }
+            impl<'bound, #bounds> #x<'bound> for Client<T, E> where 'bound: 'static{
+                type Error = E;
+                #(#methods)*
+            }
+        };
+    }
+}
@@ -0,0 +1,251 @@
+//! Runtime support types for generated glue.
+//!
+//! ## Handles
+//!
+//! [`handle_table`] generates `HandleTable<T>`, a generational slab mapping `u32`
+//! handles to values, modelling the ownership of PIT resources across a boundary:
+//! - `insert` hands a value over, returning a new owned handle
+//! - `borrow` and `borrow_mut` access the value of a handle, as for borrowed resources
+//! - `take` consumes an owned handle, returning its value
+//! - `drop` consumes an owned handle, dropping its value
+//!
+//! Handles are never `0`, which denotes a null resource. The low 20 bits of a handle
+//! hold its slot index plus one, and the high 12 bits the generation of the slot,
+//! which changes whenever the slot is freed. Using a handle after it was taken or
+//! dropped thus fails with `HandleError::Stale`, unless its slot was reused 4096
+//! times in between.
+//!
+//! ## Untyped resources
+//!
+//! [`any_resource`] generates `AnyResource`, the type of untyped resources in the
+//! generated traits. It carries the resource ID of the interface of its object, and
+//! is downcast with `downcast::<T>()`, `downcast_ref::<T>()` or `downcast_mut::<T>()`
+//! to any `T: Resource` with a matching resource ID. [`resource`] implements
+//! `Resource` for the dyn-compatible trait objects of an interface; other types
+//! can implement it by hand, taking the ID from the `P<hex>Meta::RID` const.
+
+use pit_core::Interface;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{FeatureFlags, Params, alloc, name};
+
+/// Generates the `HandleTable<T>` and `HandleError` types.
+///
+/// This should be generated once per module, alongside the code using it such as
+/// [`crate::guest::imports`].
+pub fn handle_table(p: &Params) -> TokenStream {
+    let core = &p.core;
+    let alloc = alloc(p);
+    quote! {
+        /// An error raised when resolving a handle in a [`HandleTable`].
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::marker::Copy, #core::cmp::PartialEq, #core::cmp::Eq)]
+        #[non_exhaustive]
+        pub enum HandleError{
+            /// The handle is `0`, denoting a null resource.
+            Null,
+            /// The handle was never issued by the table.
+            Invalid(#core::primitive::u32),
+            /// The handle was already taken or dropped.
+            Stale(#core::primitive::u32),
+        }
+        /// A generational slab mapping non-zero `u32` handles to values.
+        ///
+        /// Each handle is owned by whoever received it from [`HandleTable::insert`],
+        /// and is released by exactly one call to [`HandleTable::take`] or [`HandleTable::drop`].
+        /// Later uses of the handle fail with [`HandleError::Stale`].
+        #[derive(#core::fmt::Debug)]
+        pub struct HandleTable<T>{
+            entries: #alloc::vec::Vec<(#core::primitive::u32, #core::option::Option<T>)>,
+            free: #alloc::vec::Vec<#core::primitive::u32>,
+        }
+        const _: () = {
+            impl #core::fmt::Display for HandleError{
+                fn fmt(&self, f: &mut #core::fmt::Formatter) -> #core::fmt::Result{
+                    match self{
+                        HandleError::Null => f.write_str("null PIT handle"),
+                        HandleError::Invalid(h) => #core::write!(f, "invalid PIT handle {:#x}", h),
+                        HandleError::Stale(h) => #core::write!(f, "PIT handle {:#x} used after being taken or dropped", h),
+                    }
+                }
+            }
+            impl #core::error::Error for HandleError{}
+            impl<T> #core::default::Default for HandleTable<T>{
+                fn default() -> Self{
+                    Self::new()
+                }
+            }
+            impl<T> HandleTable<T>{
+                const INDEX_BITS: #core::primitive::u32 = 20;
+                const INDEX_MASK: #core::primitive::u32 = (1 << Self::INDEX_BITS) - 1;
+                /// Creates an empty table.
+                pub const fn new() -> Self{
+                    Self{
+                        entries: #alloc::vec::Vec::new(),
+                        free: #alloc::vec::Vec::new(),
+                    }
+                }
+                /// Returns the number of live handles.
+                pub fn len(&self) -> #core::primitive::usize{
+                    self.entries.len() - self.free.len()
+                }
+                /// Returns whether the table has no live handles.
+                pub fn is_empty(&self) -> #core::primitive::bool{
+                    self.len() == 0
+                }
+                /// Inserts `value`, returning a new owned handle to it.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the table already holds 2^20 - 1 live handles.
+                pub fn insert(&mut self, value: T) -> #core::primitive::u32{
+                    let index = match self.free.pop(){
+                        #core::option::Option::Some(a) => a,
+                        #core::option::Option::None => {
+                            let a = self.entries.len() as #core::primitive::u32;
+                            #core::assert!(a < Self::INDEX_MASK, "PIT handle table is full");
+                            self.entries.push((0, #core::option::Option::None));
+                            a
+                        }
+                    };
+                    let e = &mut self.entries[index as #core::primitive::usize];
+                    e.1 = #core::option::Option::Some(value);
+                    (e.0 << Self::INDEX_BITS) | (index + 1)
+                }
+                /// Returns the index of the live slot of `handle`.
+                fn index(&self, handle: #core::primitive::u32) -> #core::result::Result<#core::primitive::usize, HandleError>{
+                    let index = match (handle, handle & Self::INDEX_MASK){
+                        (0, _) => return #core::result::Result::Err(HandleError::Null),
+                        (_, 0) => return #core::result::Result::Err(HandleError::Invalid(handle)),
+                        (_, a) => (a - 1) as #core::primitive::usize,
+                    };
+                    match self.entries.get(index){
+                        #core::option::Option::None => #core::result::Result::Err(HandleError::Invalid(handle)),
+                        #core::option::Option::Some((g, #core::option::Option::Some(_))) if *g == handle >> Self::INDEX_BITS => #core::result::Result::Ok(index),
+                        #core::option::Option::Some(_) => #core::result::Result::Err(HandleError::Stale(handle)),
+                    }
+                }
+                /// Returns the value of `handle`.
+                pub fn borrow(&self, handle: #core::primitive::u32) -> #core::result::Result<&T, HandleError>{
+                    let index = self.index(handle)?;
+                    self.entries[index].1.as_ref().ok_or(HandleError::Stale(handle))
+                }
+                /// Returns the value of `handle` mutably.
+                pub fn borrow_mut(&mut self, handle: #core::primitive::u32) -> #core::result::Result<&mut T, HandleError>{
+                    let index = self.index(handle)?;
+                    self.entries[index].1.as_mut().ok_or(HandleError::Stale(handle))
+                }
+                /// Releases `handle`, returning its value.
+                pub fn take(&mut self, handle: #core::primitive::u32) -> #core::result::Result<T, HandleError>{
+                    let index = self.index(handle)?;
+                    let e = &mut self.entries[index];
+                    let value = e.1.take().ok_or(HandleError::Stale(handle))?;
+                    e.0 = (e.0 + 1) & (#core::primitive::u32::MAX >> Self::INDEX_BITS);
+                    self.free.push(index as #core::primitive::u32);
+                    #core::result::Result::Ok(value)
+                }
+                /// Releases `handle`, dropping its value.
+                pub fn drop(&mut self, handle: #core::primitive::u32) -> #core::result::Result<(), HandleError>{
+                    self.take(handle).map(#core::mem::drop)
+                }
+            }
+        };
+    }
+}
+
+/// Generates the `Resource` trait and the `AnyResource` type.
+///
+/// This should be generated once per module, alongside the traits of interfaces with
+/// untyped resources, and the [`resource`] implementations.
+pub fn any_resource(p: &Params) -> TokenStream {
+    let core = &p.core;
+    let alloc = alloc(p);
+    quote! {
+        /// Implemented by types identified by the resource ID of a PIT interface.
+        pub trait Resource{
+            /// The resource ID of the interface.
+            const RID: [#core::primitive::u8; 32];
+        }
+        /// An untyped resource: an object of an interface identified at runtime by its resource ID.
+        #[derive(#core::fmt::Debug)]
+        pub struct AnyResource{
+            rid: [#core::primitive::u8; 32],
+            object: #alloc::boxed::Box<dyn #core::any::Any>,
+        }
+        const _: () = {
+            impl AnyResource{
+                /// Wraps `object`, identified by the resource ID of `T`.
+                pub fn new<T: ?#core::marker::Sized + Resource + 'static>(object: #alloc::boxed::Box<T>) -> Self{
+                    Self{
+                        rid: T::RID,
+                        object: #alloc::boxed::Box::new(object),
+                    }
+                }
+                /// Returns the resource ID of the object's interface.
+                pub fn rid(&self) -> [#core::primitive::u8; 32]{
+                    self.rid
+                }
+                /// Returns whether the object is a `T`.
+                pub fn is<T: ?#core::marker::Sized + Resource + 'static>(&self) -> #core::primitive::bool{
+                    self.rid == T::RID && self.object.is::<#alloc::boxed::Box<T>>()
+                }
+                /// Returns the object if it is a `T`, or `self` otherwise.
+                pub fn downcast<T: ?#core::marker::Sized + Resource + 'static>(self) -> #core::result::Result<#alloc::boxed::Box<T>, Self>{
+                    if !self.is::<T>(){
+                        return #core::result::Result::Err(self);
+                    }
+                    match self.object.downcast::<#alloc::boxed::Box<T>>(){
+                        #core::result::Result::Ok(a) => #core::result::Result::Ok(*a),
+                        #core::result::Result::Err(object) => #core::result::Result::Err(Self{
+                            rid: self.rid,
+                            object,
+                        }),
+                    }
+                }
+                /// Returns the object if it is a `T`.
+                pub fn downcast_ref<T: ?#core::marker::Sized + Resource + 'static>(&self) -> #core::option::Option<&T>{
+                    match self.rid == T::RID{
+                        true => self.object.downcast_ref::<#alloc::boxed::Box<T>>().map(|a| &**a),
+                        false => #core::option::Option::None,
+                    }
+                }
+                /// Returns the object mutably if it is a `T`.
+                pub fn downcast_mut<T: ?#core::marker::Sized + Resource + 'static>(&mut self) -> #core::option::Option<&mut T>{
+                    match self.rid == T::RID{
+                        true => self.object.downcast_mut::<#alloc::boxed::Box<T>>().map(|a| &mut **a),
+                        false => #core::option::Option::None,
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// Generates the implementation of `Resource` for the dyn-compatible trait objects of
+/// a PIT interface, with any error type.
+///
+/// # Arguments
+///
+/// * `p` - Code generation parameters; [`FeatureFlags::dyn_compatible`] is always enabled
+/// * `i` - The PIT interface
+///
+/// # Returns
+///
+/// A `TokenStream` containing the trait implementation; see [`any_resource`].
+pub fn resource(p: &Params, i: &Interface) -> TokenStream {
+    let p = &Params {
+        flags: FeatureFlags {
+            dyn_compatible: true,
+            ..p.flags
+        },
+        ..p.clone()
+    };
+    let core = &p.core;
+    let root = i.rid();
+    let x = name(p, root);
+    quote! {
+        impl<E: 'static> Resource for dyn #x<'static,Error = E> + 'static{
+            const RID: [#core::primitive::u8; 32] = [#(#root),*];
+        }
+    }
+}
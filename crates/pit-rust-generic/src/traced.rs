@@ -0,0 +1,95 @@
+//! Tracing decorators for interface traits.
+//!
+//! The generated code depends on the [`tracing`](https://docs.rs/tracing) crate,
+//! which must be available to the crate including it.
+//!
+//! Each call made through `Traced<T>` runs inside a `DEBUG` span named `pit`,
+//! carrying the interface's resource ID, the method name and the primitive
+//! arguments. The result is reported as an event inside that span: primitive
+//! results at `DEBUG` level, errors at `ERROR` level.
+
+use pit_core::{Arg, Interface};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{Params, args, awaited, method_impl, name, scoped};
+
+/// Generates the `Traced<T>` wrapper type.
+///
+/// This should be generated once per module, alongside the [`traced`] implementations.
+pub fn wrapper(p: &Params) -> TokenStream {
+    let core = &p.core;
+    quote! {
+        /// Wraps an implementation of interface traits, tracing every call made through it.
+        #[derive(#core::fmt::Debug, #core::clone::Clone, #core::default::Default)]
+        pub struct Traced<T>(pub T);
+    }
+}
+
+/// Generates the implementation of the trait for `Traced<T>`, delegating to `T`.
+///
+/// # Arguments
+///
+/// * `p` - Code generation parameters, as passed to [`crate::interface`]
+/// * `i` - The PIT interface to trace
+///
+/// # Returns
+///
+/// A `TokenStream` containing the trait implementation.
+pub fn traced(p: &Params, i: &Interface) -> TokenStream {
+    let p = &scoped(p, i);
+    let root = i.rid();
+    let core = &p.core;
+    let x = name(p, root);
+    let rid = hex::encode(root);
+    let methods = i.methods.iter().map(|(m, s)| {
+        let f = format_ident!("{m}");
+        let args = args(s);
+        let fields = s
+            .params
+            .iter()
+            .zip(args.iter())
+            .filter(|(a, _)| !matches!(a, Arg::Resource { .. }))
+            .map(|(_, b)| quote! {#b = #b});
+        let call = match p.asyncness {
+            None => quote! {
+                span.in_scope(|| self.0.#f(#(#args),*))
+            },
+            Some(_) => awaited(
+                p,
+                quote! {
+                    ::tracing::Instrument::instrument(self.0.#f(#(#args),*), span.clone())
+                },
+            ),
+        };
+        let rets = (0..s.rets.len())
+            .map(|a| format_ident!("ret{a}"))
+            .collect::<Vec<_>>();
+        let pats = s.rets.iter().zip(rets.iter()).map(|(a, b)| match a {
+            Arg::Resource { .. } => quote! {_},
+            _ => quote! {#b},
+        });
+        let values = s
+            .rets
+            .iter()
+            .zip(rets.iter())
+            .filter(|(a, _)| !matches!(a, Arg::Resource { .. }))
+            .map(|(_, b)| quote! {#b = #b,});
+        let body = quote! {
+            let span = ::tracing::debug_span!("pit", rid = #rid, method = #m #(, #fields)*);
+            let r = #call;
+            match &r{
+                #core::result::Result::Ok((#(#pats),*)) => span.in_scope(|| ::tracing::debug!(#(#values)* "returned")),
+                #core::result::Result::Err(e) => span.in_scope(|| ::tracing::error!(error = %e, "failed")),
+            }
+            r
+        };
+        method_impl(p, m, s, root, body)
+    });
+    quote! {
+        impl<'bound,T: #x<'bound>> #x<'bound> for Traced<T>{
+            type Error = T::Error;
+            #(#methods)*
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Tests for [`adapter`], built and run with `rustc`.
+//!
+//! The sync and async traits are generated in the modules `sync` and `asy`, and
+//! the adapters in the crate root, which imports both.
+
+mod common;
+
+use pit_rust_generic::{
+    Params,
+    adapter::{adapters, wrappers},
+    interface, name,
+};
+
+const PIT: &str = "{read8(I32) -> (I32);make(I64) -> (Rthis);lend(Rthis&) -> (I32)}";
+
+/// Builds and runs the traits and adapters generated for [`PIT`], followed by
+/// `main`, in which `X` and `Y` stand for the sync and async trait names.
+fn run(test: &str, main: &str) {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags: Default::default(),
+        asyncness: None,
+        names: Default::default(),
+    };
+    let ap = Params {
+        asyncness: Some(Default::default()),
+        ..p.clone()
+    };
+    let s = interface(&p, &i);
+    let a = interface(&ap, &i);
+    let w = wrappers(&p);
+    let d = adapters(&p, &i);
+    let main = main
+        .replace('X', &name(&p, i.rid()).to_string())
+        .replace('Y', &name(&ap, i.rid()).to_string());
+    let src = format!(
+        "mod sync {{\n{s}\n}}\nmod asy {{\n{a}\n}}\nuse sync::*;\nuse asy::*;\n{w}\n{d}\n{main}"
+    );
+    common::run(test, &src);
+}
+
+#[test]
+fn round_trip() {
+    run(
+        "adapters",
+        "use std::{convert::Infallible, ops::DerefMut};
+        struct Counter(u32);
+        impl<'bound> X<'bound> for Counter {
+            type Error = Infallible;
+            fn read8(&mut self, a: u32) -> Result<u32, Infallible> {
+                self.0 += a;
+                Ok(self.0)
+            }
+            fn make(&mut self, a: u64) -> Result<impl X<'bound, Error = Infallible> + 'bound, Infallible> {
+                Ok(Counter(a as u32))
+            }
+            fn lend(&mut self, mut a: impl DerefMut<Target = impl X<'bound, Error = Infallible> + 'bound> + 'bound) -> Result<u32, Infallible> {
+                a.read8(self.0)
+            }
+        }
+        fn main() {
+            // The async methods of a sync implementation complete when first polled.
+            let mut a = SyncAdapter::new(Counter(0));
+            assert_eq!(Ready.block_on(Y::read8(&mut a, 1)).unwrap(), 1);
+            let mut m = Ready.block_on(Y::make(&mut a, 5)).unwrap();
+            assert_eq!(Ready.block_on(Y::read8(&mut m, 1)).unwrap(), 6);
+            // Adapting back drives the async implementation with the executor.
+            let mut b = BlockOn(a, Ready);
+            assert_eq!(X::read8(&mut b, 1).unwrap(), 2);
+            let mut m = X::make(&mut b, 7).unwrap();
+            assert_eq!(X::read8(&mut m, 1).unwrap(), 8);
+            // Borrowed resources are adapted both ways.
+            let mut c = Counter(10);
+            assert_eq!(X::lend(&mut b, &mut c).unwrap(), 12);
+            assert_eq!(c.0, 12);
+            assert_eq!(b.0.0.0, 2);
+        }",
+    );
+}
@@ -0,0 +1,70 @@
+//! Builds generated code with `rustc`, for the tests compiling or running it.
+#![allow(dead_code)]
+
+use std::{path::PathBuf, process::Command};
+
+/// Compiles `src` as the crate `name` with `args` in the directory `test` of
+/// `CARGO_TARGET_TMPDIR`, panicking with the compiler output on failure, and returns
+/// the directory.
+///
+/// Crates built earlier in the directory can be used with `extern crate`.
+fn compile_crate(test: &str, name: &str, src: &str, args: &[&str]) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join(format!("{name}.rs"));
+    std::fs::write(&file, src).unwrap();
+    let out = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned()))
+        .args(["--edition=2024", &format!("--crate-name={name}")])
+        .args(args)
+        .arg("-L")
+        .arg(&dir)
+        .arg("--out-dir")
+        .arg(&dir)
+        .arg(file)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    dir
+}
+
+/// Compiles `src` as the crate `generated`; see [`compile_crate`].
+fn compile(test: &str, src: &str, args: &[&str]) -> PathBuf {
+    compile_crate(test, "generated", src, args)
+}
+
+/// Builds `src` as the library crate `name`, for the crates built in the directory
+/// `test` afterwards.
+pub fn library(test: &str, name: &str, src: &str) {
+    compile_crate(test, name, src, &["--crate-type=rlib"]);
+}
+
+/// Type-checks `src` as a library crate.
+pub fn check(test: &str, src: &str) {
+    compile(test, src, &["--crate-type=lib", "--emit=metadata"]);
+}
+
+/// Builds `src` as a binary crate and runs it, panicking with its standard error
+/// if it fails.
+pub fn run(test: &str, src: &str) {
+    let dir = compile(test, src, &["--crate-type=bin"]);
+    let out = Command::new(dir.join("generated")).output().unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+/// Builds `src` as a binary crate and runs it, panicking unless it fails, and
+/// returns its standard error.
+pub fn fail(test: &str, src: &str) -> String {
+    let dir = compile(test, src, &["--crate-type=bin"]);
+    let out = Command::new(dir.join("generated")).output().unwrap();
+    let err = String::from_utf8_lossy(&out.stderr).into_owned();
+    assert!(!out.status.success(), "{err}");
+    err
+}
@@ -0,0 +1,39 @@
+//! Tests for parameter names and documentation taken from annotations.
+
+mod common;
+
+use pit_rust_generic::{Params, interface};
+
+const PIT: &str = "[doc=A counter.]{read[doc=Reads bytes.][p0.name=offset][p0.doc=Where to read.][p1.name=len](I64,I32) -> (I32);swap[doc=Swaps.][p0.doc=The other one.]([name=other]Rthis&,[name=self]Rthis,[name=other]Rthis) -> ()}";
+
+#[test]
+fn annotations() {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let p = Params {
+        core: syn::parse_quote!(::core),
+        flags: Default::default(),
+        asyncness: None,
+        names: Default::default(),
+    };
+    let t = interface(&p, &i).to_string();
+    // The interface, methods and documented parameters are documented.
+    assert!(t.contains("# [doc = \"A counter.\"] pub trait"), "{t}");
+    assert!(
+        t.contains(
+            "# [doc = \"Reads bytes.\"] # [doc = \"\"] # [doc = \"# Arguments\"] # [doc = \"\"] # [doc = \"* `offset` - Where to read.\"] fn read (& mut self , offset : :: core :: primitive :: u64 , len : :: core :: primitive :: u32)"
+        ),
+        "{t}"
+    );
+    // Invalid and duplicate names fall back to `argN`.
+    assert!(
+        t.contains("# [doc = \"* `other` - The other one.\"] fn swap (& mut self , other :"),
+        "{t}"
+    );
+    assert!(t.contains(", arg1 :"), "{t}");
+    assert!(t.contains(", arg2 :"), "{t}");
+    // Every item is documented.
+    common::check(
+        "docs",
+        &format!("//! Docs.\n#![deny(missing_docs)]\nextern crate alloc;\n{t}"),
+    );
+}
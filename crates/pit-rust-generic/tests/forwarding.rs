@@ -0,0 +1,78 @@
+//! Tests for [`FeatureFlags::forwarding`], built with `rustc`.
+
+mod common;
+
+use pit_rust_generic::{FeatureFlags, Params, interface, name};
+
+const PIT: &str = "{read8(I32) -> (I32);make(I64) -> (Rthis)}";
+
+/// Returns the trait generated for [`PIT`] with forwarding implementations, followed
+/// by `main`, in which `X` stands for the trait name.
+fn source(dyn_compatible: bool, asyncness: bool, main: &str) -> String {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.forwarding = true;
+    flags.dyn_compatible = dyn_compatible;
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags,
+        asyncness: asyncness.then(Default::default),
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let x = name(&p, i.rid()).to_string();
+    format!("{t}\n{}", main.replace('X', &x))
+}
+
+#[test]
+fn sync_dyn() {
+    let src = source(
+        true,
+        false,
+        "use std::{cell::RefCell, convert::Infallible, rc::Rc, sync::{Arc, Mutex}};
+        struct Counter(u32);
+        impl<'bound> X<'bound> for Counter {
+            type Error = Infallible;
+            fn read8(&mut self, a: u32) -> Result<u32, Infallible> {
+                self.0 += a;
+                Ok(self.0)
+            }
+            fn make(&mut self, a: u64) -> Result<Box<dyn X<'bound, Error = Infallible> + 'bound>, Infallible> {
+                Ok(Box::new(Counter(a as u32)))
+            }
+        }
+        fn read<'bound>(x: &mut impl X<'bound>, a: u32) -> u32 {
+            x.read8(a).ok().unwrap()
+        }
+        fn main() {
+            let mut c = Counter(0);
+            assert_eq!(read(&mut &mut c, 1), 1);
+            assert_eq!(read(&mut Box::new(&mut c), 1), 2);
+            let mut b: Box<dyn X<'static, Error = Infallible>> = Box::new(c);
+            assert_eq!(read(&mut b, 1), 3);
+            let mut r = Rc::new(RefCell::new(Counter(0)));
+            assert_eq!(read(&mut r, 2), 2);
+            assert_eq!(r.borrow().0, 2);
+            let mut a = Arc::new(Mutex::new(Counter(0)));
+            assert_eq!(read(&mut a, 3), 3);
+            assert_eq!(read(&mut a.make(4).unwrap(), 1), 5);
+            assert_eq!(a.lock().unwrap().0, 3);
+        }",
+    );
+    common::run("forwarding_sync_dyn", &src);
+}
+
+/// Methods returning `impl Trait` get no `Rc<RefCell<T>>` and `Arc<Mutex<T>>`
+/// implementations, whose results would borrow from the guard.
+#[test]
+fn async_() {
+    let src = source(
+        false,
+        true,
+        "pub fn forwarded<'a, T: X<'a>>(t: &'a mut T) -> impl X<'a> {
+            Box::new(t)
+        }",
+    );
+    common::check("forwarding_async", &src);
+    assert!(!src.contains("RefCell"), "{src}");
+}
@@ -0,0 +1,247 @@
+//! Tests for [`guest`], built natively and run with `rustc`.
+//!
+//! The generated binary stands in for the host: it defines the functions the glue
+//! imports, and calls the functions it exports.
+
+mod common;
+
+use pit_rust_generic::{
+    FeatureFlags, Params,
+    guest::{exports, import_name, imports},
+    interface, name,
+    rt::handle_table,
+};
+
+const PIT: &str =
+    "{tally(I32) -> (I32);kid() -> (Rthis&);spawn(I32) -> (Rthis);visit(Rthis&) -> (I32)}";
+
+/// Host functions counting the handles the guest has yet to release, and the
+/// functions the guest exports, prefixed with `guest_`.
+///
+/// Host handles are numbers: `kid` returns its handle plus `0x100`, and `~salt`
+/// returns the slot plus `0x10000`. `visit` calls `tally(1)` on the lent object,
+/// and records its slot in `LENT`.
+const HOST: &str = "
+use std::cell::{Cell, RefCell};
+thread_local! {
+    static LIVE: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+    static LENT: Cell<u32> = const { Cell::new(0) };
+}
+unsafe extern \"C\" {
+    #[link_name = \"pit/HEX/~salt/tally\"]
+    fn guest_tally(slot: u32, a: u32) -> u32;
+    #[link_name = \"pit/HEX/~salt/kid\"]
+    fn guest_kid(slot: u32) -> u32;
+    #[link_name = \"pit/HEX/~salt/visit\"]
+    fn guest_visit(slot: u32, a: u32) -> u32;
+    #[link_name = \"pit/HEX/~salt.drop\"]
+    fn guest_drop(slot: u32);
+}
+fn slot(handle: u32) -> u32 {
+    handle - 0x10000
+}
+fn live() -> Vec<u32> {
+    LIVE.with(|l| l.borrow().clone())
+}
+#[unsafe(no_mangle)]
+extern \"C\" fn tally(this: u32, a: u32) -> u32 {
+    this + a
+}
+#[unsafe(no_mangle)]
+extern \"C\" fn kid(this: u32) -> u32 {
+    LIVE.with(|l| l.borrow_mut().push(this + 0x100));
+    this + 0x100
+}
+#[unsafe(no_mangle)]
+extern \"C\" fn spawn(_: u32, a: u32) -> u32 {
+    LIVE.with(|l| l.borrow_mut().push(a));
+    a
+}
+#[unsafe(no_mangle)]
+extern \"C\" fn visit(_: u32, a: u32) -> u32 {
+    LENT.with(|l| l.set(slot(a)));
+    unsafe { guest_tally(slot(a), 1) }
+}
+#[unsafe(export_name = \"~drop\")]
+extern \"C\" fn pit_drop(this: u32) {
+    LIVE.with(|l| l.borrow_mut().retain(|h| *h != this));
+}
+#[unsafe(export_name = \"~salt\")]
+extern \"C\" fn pit_new(slot: u32) -> u32 {
+    slot + 0x10000
+}
+";
+
+/// The guest type, a counter with a child counter.
+const GUEST: &str = "
+use std::convert::Infallible;
+#[derive(Default)]
+struct Counter(u32, Option<Box<Counter>>);
+impl X<'static> for Counter {
+    type Error = Infallible;
+    fn tally(&mut self, a: u32) -> Result<u32, Infallible> {
+        self.0 += a;
+        Ok(self.0)
+    }
+    fn kid(&mut self) -> Result<&mut (dyn X<'static, Error = Infallible> + 'static), Infallible> {
+        Ok(self.1.get_or_insert_default().as_mut())
+    }
+    fn spawn(&mut self, a: u32) -> Result<Box<dyn X<'static, Error = Infallible> + 'static>, Infallible> {
+        Ok(Box::new(Counter(a, None)))
+    }
+    fn visit(&mut self, a: &mut (dyn X<'static, Error = Infallible> + 'static)) -> Result<u32, Infallible> {
+        a.tally(self.0)
+    }
+}
+";
+
+/// Returns the glue generated for [`PIT`] followed by [`HOST`], [`GUEST`] and `main`,
+/// in which `X` stands for the trait name and `XImport` for the handle type.
+fn source(main: &str) -> String {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.dyn_compatible = true;
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags,
+        asyncness: None,
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let h = handle_table(&p);
+    let g = imports(&p, &i, "salt").unwrap();
+    let e = exports(&p, &i, "salt", &syn::parse_quote!(Counter)).unwrap();
+    let x = name(&p, i.rid()).to_string();
+    let y = import_name(&p, i.rid()).to_string();
+    let main = format!("{HOST}\n{GUEST}\n{main}")
+        .replace("HEX", &hex::encode(i.rid()))
+        .replace("XImport", &y)
+        .replace('X', &x);
+    format!("{t}\n{h}\n{g}\n{e}\n{main}")
+}
+
+/// Builds and runs [`source`].
+fn run(test: &str, main: &str) {
+    common::run(test, &source(main));
+}
+
+#[test]
+fn borrowed_results() {
+    run(
+        "borrowed_results",
+        "fn main() {
+            let mut a = unsafe { XImport::from_raw(1) };
+            let k = a.kid().unwrap();
+            assert_eq!(k.tally(2).unwrap(), 0x103);
+            assert_eq!(live(), [0x101]);
+            // Borrowed results are released by the next call on their parent.
+            assert_eq!(a.tally(2).unwrap(), 3);
+            assert_eq!(live(), []);
+            a.kid().unwrap();
+            a.kid().unwrap().kid().unwrap();
+            assert_eq!(live(), [0x101, 0x201]);
+            // And by the release of their parent.
+            drop(a);
+            assert_eq!(live(), []);
+            // Owned results are released when dropped.
+            let mut b = unsafe { XImport::from_raw(2) };
+            let s = b.spawn(7).unwrap();
+            drop(b);
+            assert_eq!(live(), [7]);
+            drop(s);
+            assert_eq!(live(), []);
+        }",
+    );
+}
+
+#[test]
+fn borrows() {
+    run(
+        "borrows",
+        "fn main() {
+            let h = XImport::from(Counter::default()).into_raw();
+            let s = slot(h);
+            unsafe {
+                assert_eq!(guest_tally(s, 2), 2);
+                // Borrowed results stay usable until their parent is called again.
+                let c = slot(guest_kid(s));
+                assert_eq!(guest_tally(c, 3), 3);
+                let g = slot(guest_kid(c));
+                assert_eq!(guest_tally(g, 4), 4);
+                assert_eq!(guest_tally(g, 4), 8);
+                assert_eq!(guest_tally(c, 3), 6);
+                let g = slot(guest_kid(c));
+                assert_eq!(guest_tally(g, 4), 12);
+                // Borrowed parameters are only usable during the call.
+                let mut a = XImport::from_raw(5);
+                let mut local = Counter::default();
+                assert_eq!(a.visit(&mut local).unwrap(), 1);
+                assert_eq!(local.0, 1);
+                assert_eq!(guest_visit(s, 5), 7);
+                guest_drop(s);
+            }
+        }",
+    );
+}
+
+/// Calls through slots whose borrow has ended trap.
+#[test]
+fn ended() {
+    let cases = [
+        (
+            "ended_parent_called",
+            "let c = slot(guest_kid(s));
+            guest_tally(s, 0);
+            guest_tally(c, 0);",
+        ),
+        (
+            "ended_grandparent_called",
+            "let c = slot(guest_kid(s));
+            let g = slot(guest_kid(c));
+            guest_tally(s, 0);
+            guest_tally(g, 0);",
+        ),
+        (
+            "ended_borrowed_from_invalidated",
+            "let c = slot(guest_kid(s));
+            guest_tally(s, 0);
+            guest_kid(c);",
+        ),
+        (
+            "ended_parent_released",
+            "let c = slot(guest_kid(s));
+            guest_drop(s);
+            guest_tally(c, 0);",
+        ),
+        (
+            "ended_lent",
+            "XImport::from_raw(5).visit(&mut Counter::default()).unwrap();
+            guest_tally(LENT.with(Cell::get), 0);",
+        ),
+    ];
+    for (test, case) in cases {
+        let main = format!(
+            "fn main() {{
+                let s = slot(XImport::from(Counter::default()).into_raw());
+                unsafe {{
+                    {case}
+                }}
+            }}"
+        );
+        let err = common::fail(test, &source(&main));
+        assert!(err.contains("used after its borrow ended"), "{test}: {err}");
+    }
+    let err = common::fail(
+        "released",
+        &source(
+            "fn main() {
+                let s = slot(XImport::from(Counter::default()).into_raw());
+                unsafe {
+                    guest_drop(s);
+                    guest_tally(s, 0);
+                }
+            }",
+        ),
+    );
+    assert!(err.contains("used after being taken or dropped"), "{err}");
+}
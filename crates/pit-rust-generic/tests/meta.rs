@@ -0,0 +1,39 @@
+//! Tests for the `P<hex>Meta` struct generated by [`interface`], built and run with `rustc`.
+
+mod common;
+
+use pit_rust_generic::{FeatureFlags, Params, interface, name};
+
+#[test]
+fn consts() {
+    let (_, i) = pit_core::parse_interface("{read8(I32) -> (I32);make(I64) -> (Rthis)}").unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.dyn_compatible = true;
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags,
+        asyncness: None,
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let x = name(&p, i.rid());
+    let methods = i
+        .methods
+        .iter()
+        .map(|(a, b)| format!("({a:?}, {:?})", b.to_string()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // The metadata does not make the trait dyn-incompatible.
+    let src = format!(
+        "{t}
+        fn main() {{
+            let _: Option<Box<dyn {x}<'static, Error = std::convert::Infallible>>> = None;
+            assert_eq!({x}Meta::RID, {rid:?});
+            assert_eq!({x}Meta::PIT, {pit:?});
+            assert_eq!({x}Meta::METHODS, [{methods}]);
+        }}",
+        rid = i.rid(),
+        pit = i.to_string(),
+    );
+    common::run("meta", &src);
+}
@@ -0,0 +1,135 @@
+//! Tests for [`mock`], built and run with `rustc`.
+//!
+//! Each test generates a trait and its mock with the given flags, together with
+//! a `main` using the mock through code generic over the trait.
+
+mod common;
+
+use pit_rust_generic::{FeatureFlags, Params, interface, mock::mock, name};
+
+const PIT: &str =
+    "[name=Rich]{read8(I32) -> (I32);make(I64) -> (Rthis, F32);lend(Rthis&) -> (I32)}";
+
+/// Builds and runs the trait and mock generated for [`PIT`] followed by `main`,
+/// in which `X` stands for the trait name.
+fn run(test: &str, dyn_compatible: bool, main: &str) {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.dyn_compatible = dyn_compatible;
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags,
+        asyncness: None,
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let m = mock(&p, &i);
+    let x = name(&p, i.rid()).to_string();
+    let src = format!("{t}\n{m}\n{}", main.replace('X', &x));
+    common::run(test, &src);
+}
+
+#[test]
+fn generic() {
+    run(
+        "generic",
+        false,
+        "fn sum<'bound, T: X<'bound>>(t: &mut T) -> Result<u32, T::Error> {
+            let (mut child, _) = t.make(1)?;
+            Ok(t.read8(1)? + child.read8(2)?)
+        }
+        fn main() {
+            let mut m = MockRich::new();
+            m.return_read8(Ok(1)).expect_make(|a| {
+                assert_eq!(a, 1);
+                let mut c = MockRich::new();
+                c.on_read8(|a| Ok(a * 10));
+                Ok((c, 0.5))
+            });
+            assert_eq!(sum(&mut m).unwrap(), 21);
+            m.assert_called(\"make\", 1);
+            assert_eq!(m.calls()[1], MockRichCall::read8(1));
+            // Resource arguments are passed to the closures as `()`.
+            m.expect_lend(|()| Ok(3));
+            assert_eq!(m.lend(Box::new(MockRich::new())).unwrap(), 3);
+            let e = sum(&mut m).unwrap_err();
+            assert_eq!(e.to_string(), \"`make` is not implemented for X\");
+            let mut m = MockRich::with_unimplemented(|m| std::io::Error::other(m));
+            assert_eq!(sum(&mut m).unwrap_err().to_string(), \"make\");
+        }",
+    );
+}
+
+#[test]
+fn dyn_() {
+    run(
+        "dyn",
+        true,
+        "fn main() {
+            let mut m = MockRich::new();
+            m.expect_lend(|a| a.read8(4));
+            let mut other = MockRich::new();
+            other.on_read8(|a| Ok(a + 1));
+            let t: &mut dyn X<'_, Error = _> = &mut m;
+            assert_eq!(t.lend(&mut other).unwrap(), 5);
+            other.assert_called(\"read8\", 1);
+        }",
+    );
+}
+
+/// Mocks returning the mocks of other interfaces, with specialization.
+#[test]
+fn two_interfaces() {
+    let (_, y) = pit_core::parse_interface("[name=Size]{size() -> (I32)}").unwrap();
+    let (_, x) = pit_core::parse_interface(&format!(
+        "[name=Sizes]{{get(I32) -> (R{0});lend(R{0}&) -> (I32)}}",
+        hex::encode(y.rid())
+    ))
+    .unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.specialization = true;
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags,
+        asyncness: None,
+        names: Default::default(),
+    };
+    let mut src = "#![feature(specialization)]\n#![allow(incomplete_features)]\n".to_owned();
+    for i in [&x, &y] {
+        src += &format!("{}\n{}\n", interface(&p, i), mock(&p, i));
+    }
+    src += &"#[derive(Debug)]
+        struct E(String);
+        impl std::fmt::Display for E {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+        impl std::error::Error for E {}
+        impl From<XError> for E {
+            fn from(e: XError) -> Self {
+                E(e.to_string())
+            }
+        }
+        impl From<YError> for E {
+            fn from(e: YError) -> Self {
+                E(e.to_string())
+            }
+        }
+        fn main() {
+            let mut m = MockSizes::with_unimplemented(|m| E(m.to_owned()));
+            m.expect_get(|a| {
+                let mut y = MockSize::with_unimplemented(|m| E(m.to_owned()));
+                y.return_size(Ok(a));
+                Ok(y)
+            });
+            let mut y = Sizes::get(&mut m, 3).unwrap();
+            assert_eq!(Size::size(&mut y).unwrap(), 3);
+            assert_eq!(Size::size(&mut y).unwrap_err().0, \"size\");
+            m.expect_lend(|()| Ok(1));
+            assert_eq!(Sizes::lend(&mut m, Box::new(MockSize::with_unimplemented(|m| E(m.to_owned())))).unwrap(), 1);
+        }"
+    .replace("XError", &format!("{}Error", name(&p, x.rid())))
+    .replace("YError", &format!("{}Error", name(&p, y.rid())));
+    common::run("two_interfaces", &src);
+}
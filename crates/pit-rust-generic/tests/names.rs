@@ -0,0 +1,50 @@
+//! Tests for readable names taken from `[name=...]` annotations.
+
+use pit_rust_generic::{GenError, Params, interface, name, readable_name, try_interface};
+
+fn params() -> Params {
+    Params {
+        core: syn::parse_quote!(::core),
+        flags: Default::default(),
+        asyncness: None,
+        names: Default::default(),
+    }
+}
+
+#[test]
+fn valid() {
+    let (_, i) = pit_core::parse_interface("[name=Buffer]{size() -> (I32)}").unwrap();
+    let p = params();
+    assert_eq!(readable_name(&p, &i).unwrap(), "Buffer");
+    let t = try_interface(&p, &i).unwrap().to_string();
+    assert!(t.contains("pub trait Buffer"), "{t}");
+}
+
+#[test]
+fn invalid() {
+    let (_, i) = pit_core::parse_interface("[name=my-buffer]{size() -> (I32)}").unwrap();
+    let p = params();
+    assert_eq!(readable_name(&p, &i), None);
+    assert_eq!(
+        try_interface(&p, &i).unwrap_err(),
+        GenError::InvalidName {
+            name: "my-buffer".to_owned()
+        }
+    );
+    // The infallible version falls back to the generated name.
+    let x = name(&p, i.rid());
+    let t = interface(&p, &i).to_string();
+    assert!(t.contains(&format!("pub trait {x}")), "{t}");
+}
+
+#[test]
+fn overridden() {
+    let (_, i) = pit_core::parse_interface("[name=my-buffer]{size() -> (I32)}").unwrap();
+    let mut p = params();
+    p.names.insert(
+        i.rid(),
+        proc_macro2::Ident::new("Buffer", proc_macro2::Span::call_site()),
+    );
+    let t = try_interface(&p, &i).unwrap().to_string();
+    assert!(t.contains("pub trait Buffer"), "{t}");
+}
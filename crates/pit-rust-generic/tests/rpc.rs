@@ -0,0 +1,98 @@
+//! Tests for [`rpc`], built and run with `rustc`.
+//!
+//! The generated binary serves objects between the two peers of a `Loopback`.
+
+mod common;
+
+use pit_rust_generic::{
+    FeatureFlags, Params, interface, name,
+    rpc::{rpc, runtime},
+    rt::handle_table,
+};
+
+// `call` and `send` share their names with the methods of `Client`.
+const PIT: &str =
+    "{call(I32) -> (I32);send(I32) -> (I32);make(I64) -> (Rthis);lend(Rthis&) -> (I32)}";
+
+/// Builds and runs the code generated for [`PIT`] followed by `main`, in which
+/// `X` stands for the trait name.
+fn run(test: &str, main: &str) {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.dyn_compatible = true;
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags,
+        asyncness: None,
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let h = handle_table(&p);
+    let r = runtime(&p);
+    let c = rpc(&p, &i);
+    let x = name(&p, i.rid()).to_string();
+    let src = format!("{t}\n{h}\n{r}\n{c}\n{}", main.replace('X', &x));
+    common::run(test, &src);
+}
+
+#[test]
+fn loopback() {
+    run(
+        "loopback",
+        "#[derive(Debug)]
+        struct E(RpcError);
+        impl std::fmt::Display for E {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+        impl std::error::Error for E {}
+        impl From<RpcError> for E {
+            fn from(e: RpcError) -> Self {
+                E(e)
+            }
+        }
+        type Object = dyn X<'static, Error = E>;
+        struct Counter(u32);
+        impl X<'static> for Counter {
+            type Error = E;
+            fn call(&mut self, a: u32) -> Result<u32, E> {
+                self.0 += a;
+                Ok(self.0)
+            }
+            fn send(&mut self, a: u32) -> Result<u32, E> {
+                Ok(a * 2)
+            }
+            fn make(&mut self, a: u64) -> Result<Box<Object>, E> {
+                Ok(Box::new(Counter(a as u32)))
+            }
+            fn lend(&mut self, a: &mut Object) -> Result<u32, E> {
+                a.call(self.0)
+            }
+        }
+        fn main() {
+            let (a, b) = Loopback::<E>::pair();
+            let h = a.export(Box::new(Box::new(Counter(10)) as Box<Object>));
+            let mut c = Client::new(b.clone(), h);
+            // The methods of `Client` shadow `call` and `send` outside of the trait.
+            assert_eq!(X::call(&mut c, 1).unwrap(), 11);
+            assert_eq!(X::send(&mut c, 4).unwrap(), 8);
+            // Made objects stay served until their client is dropped.
+            let mut made = c.make(5).unwrap();
+            assert_eq!(made.call(1).unwrap(), 6);
+            assert_eq!(a.objects.borrow().len(), 2);
+            // Lent objects are served by the caller for the duration of the call.
+            let mut local = Counter(100);
+            assert_eq!(c.lend(&mut local).unwrap(), 111);
+            assert_eq!(local.0, 111);
+            assert_eq!(b.objects.borrow().len(), 0);
+            assert_eq!(made.lend(&mut c).unwrap(), 17);
+            assert_eq!(a.objects.borrow().len(), 2);
+            drop(made);
+            assert_eq!(a.objects.borrow().len(), 1);
+            drop(c);
+            assert_eq!(a.objects.borrow().len(), 0);
+            assert!(X::call(&mut Client::new(b, h), 1).is_err());
+        }",
+    );
+}
@@ -0,0 +1,109 @@
+//! Tests for [`rt`], built and run with `rustc`.
+
+mod common;
+
+use pit_rust_generic::{
+    FeatureFlags, Params, interface, name,
+    rt::{any_resource, handle_table, resource},
+};
+
+/// Returns the parameters the runtime types are generated with.
+fn params() -> Params {
+    Params {
+        core: syn::parse_quote!(::std),
+        flags: Default::default(),
+        asyncness: None,
+        names: Default::default(),
+    }
+}
+
+#[test]
+fn handles() {
+    let h = handle_table(&params());
+    let src = format!(
+        "{h}
+        fn main() {{
+            let mut t = HandleTable::new();
+            // The low 20 bits hold the index plus one, the high 12 bits the generation.
+            let a = t.insert('a');
+            let b = t.insert('b');
+            assert_eq!((a, b), (1, 2));
+            assert_eq!(t.borrow(b), Ok(&'b'));
+            *t.borrow_mut(a).unwrap() = 'c';
+            assert_eq!(t.take(a), Ok('c'));
+            assert_eq!(t.len(), 1);
+            // Freed slots are reused with the next generation.
+            let c = t.insert('d');
+            assert_eq!(c, 1 << 20 | 1);
+            assert_eq!(t.borrow(c), Ok(&'d'));
+            // Handles of freed slots are stale, even once the slot is reused.
+            assert_eq!(t.borrow(a), Err(HandleError::Stale(a)));
+            assert_eq!(t.take(a), Err(HandleError::Stale(a)));
+            assert_eq!(t.drop(c), Ok(()));
+            assert_eq!(t.drop(c), Err(HandleError::Stale(c)));
+            assert_eq!(t.borrow(0), Err(HandleError::Null));
+            assert_eq!(t.borrow(3), Err(HandleError::Invalid(3)));
+            assert_eq!(t.borrow(1 << 20), Err(HandleError::Invalid(1 << 20)));
+            // Generations wrap after 4096 reuses, when stale handles become valid again.
+            for g in 2..4096 {{
+                let d = t.insert('e');
+                assert_eq!(d, g << 20 | 1);
+                t.drop(d).unwrap();
+            }}
+            let d = t.insert('f');
+            assert_eq!(d, a);
+            assert_eq!(t.borrow(a), Ok(&'f'));
+            assert_eq!(t.len(), 2);
+            assert!(!t.is_empty());
+        }}"
+    );
+    common::run("handles", &src);
+}
+
+#[test]
+fn any() {
+    let (_, a) = pit_core::parse_interface("{size() -> (I32)}").unwrap();
+    let (_, b) = pit_core::parse_interface("{hold(R_) -> (R_)}").unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.dyn_compatible = true;
+    let p = Params { flags, ..params() };
+    let r = any_resource(&p);
+    let (ta, tb) = (interface(&p, &a), interface(&p, &b));
+    let (ra, rb) = (resource(&p, &a), resource(&p, &b));
+    let (x, y) = (name(&p, a.rid()), name(&p, b.rid()));
+    let src = format!(
+        "{r}\n{ta}\n{tb}\n{ra}\n{rb}
+        use std::convert::Infallible;
+        type A = dyn {x}<'static, Error = Infallible>;
+        type B = dyn {y}<'static, Error = Infallible>;
+        struct Size(u32);
+        impl {x}<'static> for Size {{
+            type Error = Infallible;
+            fn size(&mut self) -> Result<u32, Infallible> {{
+                Ok(self.0)
+            }}
+        }}
+        // Untyped resources are passed as `AnyResource`.
+        struct Holder;
+        impl {y}<'static> for Holder {{
+            type Error = Infallible;
+            fn hold(&mut self, a: AnyResource) -> Result<AnyResource, Infallible> {{
+                Ok(a)
+            }}
+        }}
+        fn main() {{
+            let mut r = Holder.hold(AnyResource::new::<A>(Box::new(Size(3)))).unwrap();
+            assert_eq!(r.rid(), {rid:?});
+            assert!(r.is::<A>());
+            assert!(!r.is::<B>());
+            // The error type is part of the type of the object.
+            assert!(!r.is::<dyn {x}<'static, Error = std::fmt::Error>>());
+            assert!(r.downcast_ref::<B>().is_none());
+            assert_eq!(r.downcast_mut::<A>().unwrap().size(), Ok(3));
+            let r = r.downcast::<B>().err().unwrap();
+            assert_eq!(r.downcast::<A>().ok().unwrap().size(), Ok(3));
+        }}",
+        rid = a.rid(),
+    );
+    common::run("any", &src);
+}
@@ -0,0 +1,70 @@
+//! Compile tests for [`FeatureFlags::signed`] and `sign` annotations.
+//!
+//! Each test generates a trait together with an implementation spelling out the
+//! integer types, and type-checks it with `rustc`.
+
+mod common;
+
+use pit_rust_generic::{FeatureFlags, Params, interface, name};
+
+/// Type-checks the trait generated for `pit` followed by `imp`, in which `X`
+/// stands for the trait name.
+fn check(test: &str, pit: &str, flags: FeatureFlags, imp: &str) {
+    let (_, i) = pit_core::parse_interface(pit).unwrap();
+    let p = Params {
+        core: syn::parse_quote!(::core),
+        flags,
+        asyncness: None,
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let x = name(&p, i.rid()).to_string();
+    let src = format!(
+        "#![feature(specialization)]\n#![allow(incomplete_features)]\nextern crate alloc;\n{t}\n{}",
+        imp.replace('X', &x)
+    );
+    common::check(test, &src);
+}
+
+/// Annotations on methods override the one on the interface, and annotations on
+/// values override both.
+#[test]
+fn annotations() {
+    check(
+        "signed_annotations",
+        "[sign=s]{delta[r0.sign=u](I32,I64) -> (I32,I64);status[sign=u][p1.sign=s](I32,I32) -> (I32)}",
+        FeatureFlags::default(),
+        "pub struct Impl;
+        impl<'bound> X<'bound> for Impl {
+            type Error = core::convert::Infallible;
+            fn delta(&mut self, a: i32, b: i64) -> Result<(u32, i64), Self::Error> {
+                Ok((a as u32, b))
+            }
+            fn status(&mut self, a: u32, b: i32) -> Result<u32, Self::Error> {
+                Ok(a + b as u32)
+            }
+        }",
+    );
+}
+
+/// The default of [`FeatureFlags::signed`] applies to the specialization defaults.
+#[test]
+fn default() {
+    let mut flags = FeatureFlags::default();
+    flags.signed = true;
+    flags.specialization = true;
+    check(
+        "signed_default",
+        "{read(I32) -> (I64);size[sign=u]() -> (I64)}",
+        flags,
+        "pub struct Partial;
+        impl<'bound> X<'bound> for Partial {
+            fn read(&mut self, a: i32) -> Result<i64, Self::Error> {
+                Ok(a.into())
+            }
+        }
+        pub fn size(p: &mut Partial) -> Result<u64, XError> {
+            X::size(p)
+        }",
+    );
+}
@@ -0,0 +1,100 @@
+//! Compile tests for [`FeatureFlags::specialization`].
+//!
+//! Each test generates a trait in specialization mode, together with a partial
+//! implementation relying on the default methods, and type-checks it with `rustc`.
+
+mod common;
+
+use pit_rust_generic::{FeatureFlags, Params, interface, name};
+
+const PIT: &str =
+    "{read8(I32) -> (I32);write8(I32,I32) -> ();make(I64) -> (Rthis);lend(Rthis&) -> (I32)}";
+
+/// Type-checks the trait generated for [`PIT`] followed by `partial`, in which
+/// `X` stands for the trait name.
+fn check(test: &str, dyn_compatible: bool, asyncness: bool, partial: &str) {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.specialization = true;
+    flags.dyn_compatible = dyn_compatible;
+    let p = Params {
+        core: syn::parse_quote!(::core),
+        flags,
+        asyncness: asyncness.then(Default::default),
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let x = name(&p, i.rid()).to_string();
+    let src = format!(
+        "#![feature(specialization)]\n#![allow(incomplete_features)]\nextern crate alloc;\n{t}\n{}",
+        partial.replace('X', &x)
+    );
+    common::check(test, &src);
+}
+
+#[test]
+fn sync() {
+    check(
+        "sync",
+        false,
+        false,
+        "pub struct Partial;
+        impl<'bound> X<'bound> for Partial {
+            fn read8(&mut self, a: u32) -> Result<u32, Self::Error> {
+                Ok(a)
+            }
+            fn make(&mut self, _: u64) -> Result<impl X<'bound, Error = Self::Error> + 'bound, Self::Error> {
+                Ok(Partial)
+            }
+        }",
+    );
+}
+
+#[test]
+fn sync_dyn() {
+    check(
+        "sync_dyn",
+        true,
+        false,
+        "pub struct Partial;
+        impl<'bound> X<'bound> for Partial {
+            fn read8(&mut self, a: u32) -> Result<u32, Self::Error> {
+                Ok(a)
+            }
+        }",
+    );
+}
+
+/// Async methods return boxed futures, as `async fn`s cannot be specialized.
+#[test]
+fn async_() {
+    check(
+        "async_",
+        false,
+        true,
+        "pub struct Partial;
+        impl<'bound> X<'bound> for Partial {
+            fn read8<'life>(&'life mut self, a: u32) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = Result<u32, Self::Error>> + 'life>> where 'bound: 'life {
+                alloc::boxed::Box::pin(async move { Ok(a) })
+            }
+            fn make<'life>(&'life mut self, _: u64) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = Result<impl X<'bound, Error = Self::Error> + 'bound, Self::Error>> + 'life>> where 'bound: 'life {
+                alloc::boxed::Box::pin(async move { Ok(Partial) })
+            }
+        }",
+    );
+}
+
+#[test]
+fn async_dyn() {
+    check(
+        "async_dyn",
+        true,
+        true,
+        "pub struct Partial;
+        impl<'bound> X<'bound> for Partial {
+            fn read8<'life>(&'life mut self, a: u32) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = Result<u32, Self::Error>> + 'life>> where 'bound: 'life {
+                alloc::boxed::Box::pin(async move { Ok(a) })
+            }
+        }",
+    );
+}
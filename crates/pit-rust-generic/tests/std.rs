@@ -0,0 +1,16 @@
+//! Compile test for the default output of [`Generator`], which must build in a
+//! plain `std` crate without `extern crate alloc`.
+#![cfg(feature = "std")]
+
+mod common;
+
+use pit_rust_generic::build::Generator;
+
+#[test]
+fn generator() {
+    let t = Generator::new()
+        .dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../../pit/common"))
+        .generate()
+        .unwrap();
+    common::check("std", &t.to_string());
+}
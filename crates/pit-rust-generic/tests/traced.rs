@@ -0,0 +1,130 @@
+//! Tests for [`traced`], built and run with `rustc`.
+//!
+//! The generated code is built against a stand-in for the `tracing` crate,
+//! recording spans and events as text.
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use pit_rust_generic::{
+    FeatureFlags, Params, interface, name,
+    traced::{traced, wrapper},
+};
+
+const PIT: &str = "{read8(I32) -> (I32);make(I64) -> (Rthis)}";
+
+/// The parts of `tracing` used by the generated code. Spans and events are
+/// recorded in `LOG` as their fields, formatted as `key=value`.
+const TRACING: &str = r#"
+use std::cell::RefCell;
+thread_local! {
+    pub static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+pub fn record(s: String) {
+    LOG.with(|l| l.borrow_mut().push(s));
+}
+#[derive(Clone)]
+pub struct Span;
+impl Span {
+    pub fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+}
+pub trait Instrument: Sized {
+    fn instrument(self, _: Span) -> Self {
+        self
+    }
+}
+impl<T> Instrument for T {}
+#[macro_export]
+macro_rules! debug_span {
+    ($n:literal $(, $k:ident = $v:expr)*) => {{
+        $crate::record(format!(concat!("span ", $n $(, " ", stringify!($k), "={}")*) $(, $v)*));
+        $crate::Span
+    }};
+}
+#[macro_export]
+macro_rules! debug {
+    ($($k:ident = $v:expr,)* $m:literal) => {
+        $crate::record(format!(concat!("debug ", $m $(, " ", stringify!($k), "={}")*) $(, $v)*))
+    };
+}
+#[macro_export]
+macro_rules! error {
+    ($k:ident = %$v:expr, $m:literal) => {
+        $crate::record(format!(concat!("error ", $m, " ", stringify!($k), "={}"), $v))
+    };
+}
+"#;
+
+/// Builds and runs the trait generated for [`PIT`] and its `Traced` implementation,
+/// followed by `main`, in which `X` stands for the trait name.
+fn run(test: &str, main: &str) {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let mut flags = FeatureFlags::default();
+    flags.dyn_compatible = true;
+    let p = Params {
+        core: syn::parse_quote!(::std),
+        flags,
+        asyncness: None,
+        names: Default::default(),
+    };
+    let t = interface(&p, &i);
+    let w = wrapper(&p);
+    let d = traced(&p, &i);
+    let x = name(&p, i.rid()).to_string();
+    let rid = hex::encode(i.rid());
+    let src = format!(
+        "extern crate tracing;\n{t}\n{w}\n{d}\n{}",
+        main.replace("RID", &rid).replace('X', &x)
+    );
+    common::library(test, "tracing", TRACING);
+    common::run(test, &src);
+}
+
+#[test]
+fn calls() {
+    run(
+        "traced",
+        "use std::fmt;
+        #[derive(Debug)]
+        struct E;
+        impl fmt::Display for E {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(\"too large\")
+            }
+        }
+        impl std::error::Error for E {}
+        struct Counter;
+        impl X<'static> for Counter {
+            type Error = E;
+            fn read8(&mut self, a: u32) -> Result<u32, E> {
+                if a > 8 {
+                    return Err(E);
+                }
+                Ok(a * 2)
+            }
+            fn make(&mut self, _: u64) -> Result<Box<dyn X<'static, Error = E>>, E> {
+                Ok(Box::new(Counter))
+            }
+        }
+        fn main() {
+            let mut t = Traced(Counter);
+            assert_eq!(t.read8(2).unwrap(), 4);
+            assert!(t.read8(9).is_err());
+            t.make(1).unwrap();
+            // Resources are neither arguments nor results of the spans and events.
+            assert_eq!(
+                tracing::LOG.with(|l| l.borrow().clone()),
+                [
+                    \"span pit rid=RID method=read8 arg0=2\",
+                    \"debug returned ret0=4\",
+                    \"span pit rid=RID method=read8 arg0=9\",
+                    \"error failed error=too large\",
+                    \"span pit rid=RID method=make arg0=1\",
+                    \"debug returned\",
+                ]
+            );
+        }",
+    );
+}
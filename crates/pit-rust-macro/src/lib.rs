@@ -0,0 +1,310 @@
+//! # pit-rust-macro
+//!
+//! Procedural macros generating Rust traits from PIT (Portal Interface Types) interfaces.
+//!
+//! This crate wraps [`pit_rust_generic::interface`], so consumers do not need a build
+//! script to parse `.pit` files and include the generated code.
+//!
+//! ## Overview
+//!
+//! The macros are:
+//! - [`include_pit!`] - Generates a trait from a `.pit` file
+//! - [`pit!`] - Generates a trait from an inline PIT interface
+//! - [`any_resource!`] - Generates the `AnyResource` type of untyped resources
+//!
+//! Both expand to the `P<hex_id>` trait (see [`pit_rust_generic::interface`]) and,
+//! when a name is available, a readable alias for it. The generated code paths
+//! go through `::std`, so the macros require a `std` crate.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use pit_rust_macro::{include_pit, pit};
+//!
+//! // Defines `P<hex_id>` and `pub use P<hex_id> as Buffer`.
+//! include_pit!("pit/common/buffer.pit");
+//!
+//! pit! {
+//!     Reader64 = {
+//!         read64(I64) -> (R68da167712ddf1601aed7908c99972e62a41bdea1e28b241306a6b58d29e532d)
+//!     }
+//! }
+//! ```
+//!
+//! ## Features
+//!
+//! - `unstable-generics` - Enable generic parameter support
+
+use pit_core::Interface;
+use pit_rust_generic::{GenError, Params};
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::{
+    Ident, LitStr, Token,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+};
+
+/// Parses a PIT interface, returning the byte offset of the error on failure.
+fn parse(src: &str) -> Result<Interface, usize> {
+    match pit_core::parse_interface(src) {
+        Ok((rest, i)) => match rest.trim_start() {
+            "" => Ok(i),
+            rest => Err(src.len() - rest.len()),
+        },
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(src.len() - e.input.len()),
+        Err(nom::Err::Incomplete(_)) => Err(src.len()),
+    }
+}
+
+/// Returns the code generation parameters of the macros.
+///
+/// The expansions use `::std`, so they compile in crates without `extern crate alloc`.
+fn params() -> Params {
+    Params {
+        core: syn::parse_quote!(::std),
+        flags: Default::default(),
+        asyncness: None,
+        names: Default::default(),
+    }
+}
+
+/// Generates the trait for `i`, with an optional readable alias.
+fn expand(i: &Interface, alias: Option<Ident>) -> Result<TokenStream, GenError> {
+    let p = params();
+    let x = pit_rust_generic::name(&p, i.rid());
+    let t = pit_rust_generic::try_interface(&p, i)?;
+    let readable = pit_rust_generic::readable_name(&p, i);
+    let alias = alias.filter(|a| Some(a) != readable.as_ref()).map(|a| {
+        quote! {
+            pub use #x as #a;
+        }
+    });
+    Ok(quote! {
+        #t
+        #alias
+    })
+}
+
+/// Converts a file stem such as `buffer64` into a type name such as `Buffer64`.
+fn camel(stem: &str) -> Option<Ident> {
+    let s = stem
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|a| !a.is_empty())
+        .map(|a| {
+            let mut c = a.chars();
+            match c.next() {
+                Some(f) => f.to_ascii_uppercase().to_string() + c.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<String>();
+    match s.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => Some(format_ident!("{s}")),
+        _ => None,
+    }
+}
+
+/// Input of [`include_pit!`]: `"path"` optionally followed by `as Name`.
+struct IncludePit {
+    path: LitStr,
+    alias: Option<Ident>,
+}
+impl Parse for IncludePit {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let alias = match input.parse::<Option<Token![as]>>()? {
+            Some(_) => Some(input.parse()?),
+            None => None,
+        };
+        Ok(Self { path, alias })
+    }
+}
+
+/// Input of [`pit!`]: an interface optionally preceded by `Name =`.
+struct InlinePit {
+    alias: Option<Ident>,
+    body: TokenStream,
+}
+impl Parse for InlinePit {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let alias = match input.peek(Ident) && input.peek2(Token![=]) {
+            true => {
+                let a = input.parse()?;
+                input.parse::<Token![=]>()?;
+                Some(a)
+            }
+            false => None,
+        };
+        Ok(Self {
+            alias,
+            body: input.parse()?,
+        })
+    }
+}
+
+/// Renders tokens back into PIT source, recording the offset of each token.
+///
+/// Spaces are only inserted between adjacent words, as PIT does not allow them
+/// inside attributes.
+fn source(t: TokenStream, src: &mut String, spans: &mut Vec<(usize, Span)>, word: &mut bool) {
+    for t in t {
+        match t {
+            TokenTree::Group(g) => {
+                let (open, close) = match g.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                spans.push((src.len(), g.span_open()));
+                src.push_str(open);
+                *word = false;
+                source(g.stream(), src, spans, word);
+                spans.push((src.len(), g.span_close()));
+                src.push_str(close);
+                *word = false;
+            }
+            TokenTree::Punct(p) => {
+                spans.push((src.len(), p.span()));
+                src.push(p.as_char());
+                *word = false;
+            }
+            t => {
+                if *word {
+                    src.push(' ');
+                }
+                spans.push((src.len(), t.span()));
+                src.push_str(&t.to_string());
+                *word = true;
+            }
+        }
+    }
+}
+
+/// Generates a trait from a `.pit` file.
+///
+/// The path is relative to the directory containing the consuming crate's `Cargo.toml`.
+/// The alias defaults to the file stem in `UpperCamelCase`, and can be set with `as Name`.
+/// Parse errors point at the path, with the line and column inside the file;
+/// constructs that cannot be represented in Rust are reported as errors too.
+///
+/// # Example
+///
+/// ```ignore
+/// include_pit!("pit/common/buffer.pit");
+/// include_pit!("pit/common/buffer64.pit" as WideBuffer);
+/// ```
+#[proc_macro]
+pub fn include_pit(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let IncludePit { path, alias } = parse_macro_input!(input as IncludePit);
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let file = std::path::Path::new(&dir).join(path.value());
+    let src = match std::fs::read_to_string(&file) {
+        Ok(a) => a,
+        Err(e) => {
+            return syn::Error::new(path.span(), format!("{}: {e}", file.display()))
+                .into_compile_error()
+                .into();
+        }
+    };
+    let i = match parse(&src) {
+        Ok(a) => a,
+        Err(o) => {
+            let line = src[..o].matches('\n').count() + 1;
+            let col = o - src[..o].rfind('\n').map_or(0, |a| a + 1) + 1;
+            return syn::Error::new(
+                path.span(),
+                format!(
+                    "{}:{line}:{col}: failed to parse PIT interface",
+                    file.display()
+                ),
+            )
+            .into_compile_error()
+            .into();
+        }
+    };
+    let alias = alias.or_else(|| {
+        file.file_stem()
+            .and_then(|a| a.to_str())
+            .and_then(camel)
+    });
+    let t = match expand(&i, alias) {
+        Ok(a) => a,
+        Err(e) => {
+            return syn::Error::new(path.span(), format!("{}: {e}", file.display()))
+                .into_compile_error()
+                .into();
+        }
+    };
+    let file = file.display().to_string();
+    quote! {
+        #t
+        const _: &str = ::core::include_str!(#file);
+    }
+    .into()
+}
+
+/// Generates a trait from an inline PIT interface.
+///
+/// The interface may be preceded by `Name =` to emit a readable alias.
+/// Parse errors point at the offending token.
+///
+/// # Example
+///
+/// ```ignore
+/// pit! {
+///     Buffer = {
+///         read8(I32) -> (I32);
+///         write8(I32,I32) -> ();
+///         size() -> (I32)
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn pit(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let InlinePit { alias, body } = parse_macro_input!(input as InlinePit);
+    let mut src = String::new();
+    let mut spans = Vec::new();
+    source(body, &mut src, &mut spans, &mut false);
+    match parse(&src) {
+        Ok(i) => match expand(&i, alias) {
+            Ok(a) => a.into(),
+            Err(e) => syn::Error::new(Span::call_site(), e)
+                .into_compile_error()
+                .into(),
+        },
+        Err(o) => {
+            let span = spans
+                .iter()
+                .rev()
+                .find(|(a, _)| *a <= o)
+                .map_or_else(Span::call_site, |(_, s)| *s);
+            syn::Error::new(span, "failed to parse PIT interface")
+                .into_compile_error()
+                .into()
+        }
+    }
+}
+
+/// Generates the `AnyResource` type of untyped resources.
+///
+/// Traits with untyped resources refer to `AnyResource` by name, so this must be
+/// invoked once in every module including them; see [`pit_rust_generic::rt::any_resource`].
+///
+/// # Example
+///
+/// ```ignore
+/// any_resource!();
+///
+/// pit! {
+///     Holder = {
+///         hold(R_) -> ()
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn any_resource(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    parse_macro_input!(input as syn::parse::Nothing);
+    pit_rust_generic::rt::any_resource(&params()).into()
+}
@@ -0,0 +1,34 @@
+//! The expansions compile in a plain `std` crate, without `extern crate alloc`.
+
+use pit_rust_macro::{any_resource, include_pit, pit};
+
+include_pit!("../../pit/common/buffer.pit");
+any_resource!();
+pit! {
+    Store = {
+        get(I32) -> (R867207405fe87fda620c2d7a5485e8e5e274636a898a166fb674448b4391ffc5);
+        put(R_) -> ()
+    }
+}
+
+struct Empty;
+
+impl<'bound> Buffer<'bound> for Empty {
+    type Error = P867207405fe87fda620c2d7a5485e8e5e274636a898a166fb674448b4391ffc5Error;
+    fn read8(&mut self, _: u32) -> Result<u32, Self::Error> {
+        Err(Self::Error::Transport("closed".into()))
+    }
+    fn write8(&mut self, _: u32, _: u32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    fn size(&mut self) -> Result<u32, Self::Error> {
+        Ok(0)
+    }
+}
+
+#[test]
+fn transport() {
+    let e = Empty.read8(0).unwrap_err();
+    assert_eq!(e.to_string(), "transport error: closed");
+    assert!(std::error::Error::source(&e).is_some());
+}
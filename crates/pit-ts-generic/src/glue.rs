@@ -0,0 +1,404 @@
+//! JavaScript glue for instantiating PIT WebAssembly modules.
+//!
+//! The glue follows the ABI of the guest glue of `pit-rust-generic`: resources are
+//! passed as `i32` handles, `0` denoting a null resource. For an interface with
+//! resource ID `<hex>`, the host provides the module `pit/<hex>`:
+//! - `<method>(handle, params...) -> results` - Calls a method on a host value
+//! - `~drop(handle)` - Releases a host handle held by the guest
+//! - `~<salt>(slot) -> handle` - Wraps a slot of the guest's table into a host handle
+//!
+//! and the guest exports `pit/<hex>/~<salt>/<method>(slot, params...) -> results`
+//! and `pit/<hex>/~<salt>.drop(slot)`. Methods with more than one result return
+//! them through a pointer to a struct in the guest's memory, passed before the
+//! other parameters, whose fields are aligned to their size.
+//!
+//! Handles passed as parameters are released by their sender once the call returns,
+//! unless they are owned. Handles returned are released by the receiver, borrowed
+//! ones once their borrow ends.
+//!
+//! Host values held by the guest live in the `PitHandles` table of a `PitRuntime`,
+//! shared by the glue of all interfaces of an instance. Guest objects are wrapped
+//! into objects satisfying the generated type, which release their slot when
+//! disposed with `[pitDispose]()` or garbage collected.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use pit_core::{Arg, Interface, Sig};
+
+use crate::TsOpts;
+
+/// The runtime shared by the glue of all interfaces; see [`TsOpts::runtime`].
+const RUNTIME: &str = r#"/** The key of the method disposing of a value, `Symbol.dispose` where supported. */
+export const pitDispose: symbol = (Symbol as any).dispose ?? Symbol.for("Symbol.dispose");
+/** The key of the method disposing of a value asynchronously, `Symbol.asyncDispose` where supported. */
+export const pitAsyncDispose: symbol = (Symbol as any).asyncDispose ?? Symbol.for("Symbol.asyncDispose");
+/** Makes `value` disposable, both synchronously and asynchronously, with `dispose`. */
+export function pitDisposable<T extends object>(value: T, dispose: () => void): T {
+  Object.defineProperty(value, pitDispose, { value: dispose });
+  Object.defineProperty(value, pitAsyncDispose, { value: async () => dispose() });
+  return value;
+}
+/** An error raised when resolving a handle in a `PitHandles` table. */
+export class PitHandleError extends Error {}
+/** An entry of a `PitHandles` table. */
+export type PitEntry = { value: unknown; owned: boolean };
+/**
+ * A generational slab mapping non-zero `u32` handles to values.
+ *
+ * The low 20 bits of a handle hold its slot index plus one, and the high 12 bits
+ * the generation of the slot, which changes whenever the slot is freed.
+ */
+export class PitHandles {
+  #entries: [number, PitEntry | undefined][] = [];
+  #free: number[] = [];
+  /** The number of live handles. */
+  get size(): number {
+    return this.#entries.length - this.#free.length;
+  }
+  /** Inserts `value`, returning a new handle to it. */
+  insert(value: unknown, owned: boolean): number {
+    let index = this.#free.pop();
+    if (index === undefined) {
+      index = this.#entries.length;
+      if (index >= 0xfffff) throw new PitHandleError("PIT handle table is full");
+      this.#entries.push([0, undefined]);
+    }
+    const e = this.#entries[index];
+    e[1] = { value, owned };
+    return ((e[0] << 20) | (index + 1)) >>> 0;
+  }
+  #index(handle: number): number {
+    handle >>>= 0;
+    if (handle === 0) throw new PitHandleError("null PIT handle");
+    const index = (handle & 0xfffff) - 1;
+    const e = this.#entries[index];
+    if (e === undefined) throw new PitHandleError(`invalid PIT handle 0x${handle.toString(16)}`);
+    if (e[0] !== handle >>> 20 || e[1] === undefined) {
+      throw new PitHandleError(`PIT handle 0x${handle.toString(16)} used after being taken or dropped`);
+    }
+    return index;
+  }
+  /** Returns the value of `handle`. */
+  get(handle: number): unknown {
+    return this.#entries[this.#index(handle)][1]!.value;
+  }
+  /** Releases `handle`, returning its entry. */
+  take(handle: number): PitEntry {
+    const index = this.#index(handle);
+    const e = this.#entries[index];
+    const entry = e[1]!;
+    e[1] = undefined;
+    e[0] = (e[0] + 1) & 0xfff;
+    this.#free.push(index);
+    return entry;
+  }
+}
+/** The state shared by the glue of all interfaces of a PIT wasm instance. */
+export class PitRuntime {
+  /** Host values held by the guest. */
+  readonly handles: PitHandles = new PitHandles();
+  #instance: WebAssembly.Instance | undefined;
+  #scratch: number | undefined;
+  #registry: FinalizationRegistry<() => void> = new FinalizationRegistry<() => void>((release) => release());
+  /** The instance, which must be set once instantiated before any call into the guest. */
+  get instance(): WebAssembly.Instance {
+    if (this.#instance === undefined) throw new Error("PIT runtime used before its instance was set");
+    return this.#instance;
+  }
+  set instance(instance: WebAssembly.Instance) {
+    this.#instance = instance;
+  }
+  /** Returns the exported function `name`. */
+  export(name: string): (...args: any[]) => any {
+    const f = this.instance.exports[name];
+    if (typeof f !== "function") throw new Error(`missing PIT export ${name}`);
+    return f as (...args: any[]) => any;
+  }
+  /** Returns a view of the guest's memory, valid until the guest next runs. */
+  view(): DataView {
+    return new DataView((this.instance.exports.memory as WebAssembly.Memory).buffer);
+  }
+  /** Returns the address of a page of guest memory receiving results of guest calls. */
+  scratch(): number {
+    if (this.#scratch === undefined) {
+      this.#scratch = (this.instance.exports.memory as WebAssembly.Memory).grow(1) * 65536;
+    }
+    return this.#scratch;
+  }
+  /** Hands `value` to the guest, returning a handle to it or `0` for `undefined`. */
+  lower(value: unknown, owned: boolean): number {
+    return value === undefined ? 0 : this.handles.insert(value, owned);
+  }
+  /** Returns the value of the borrowed `handle`, or `undefined` for `0`. */
+  borrow(handle: number): any {
+    return handle === 0 ? undefined : this.handles.get(handle);
+  }
+  /** Takes the value of the owned `handle` from the guest, or `undefined` for `0`. */
+  lift(handle: number): any {
+    return handle === 0 ? undefined : this.handles.take(handle).value;
+  }
+  /** Releases `handle`, disposing of its value if it is owned by the guest. */
+  drop(handle: number): void {
+    if (handle === 0) return;
+    const { value, owned } = this.handles.take(handle);
+    if (owned) (value as any)?.[pitDispose]?.();
+  }
+  /** Makes `release` run once, when `wrapper` is disposed of or garbage collected. */
+  track<T extends object>(wrapper: T, release: () => void): T {
+    let live = true;
+    const once = () => {
+      if (live) {
+        live = false;
+        release();
+      }
+    };
+    const token = {};
+    this.#registry.register(wrapper, once, token);
+    return pitDisposable(wrapper, () => {
+      this.#registry.unregister(token);
+      once();
+    });
+  }
+}
+"#;
+
+/// Returns the `DataView` accessor suffix of the ABI type of `a`.
+fn view_ty(a: &Arg) -> &'static str {
+    match a {
+        Arg::I64 => "BigInt64",
+        Arg::F32 => "Float32",
+        Arg::F64 => "Float64",
+        _ => "Int32",
+    }
+}
+
+/// Returns the offsets of the fields of the struct carrying the results of `s`.
+fn layout(s: &Sig) -> Vec<usize> {
+    let mut offset = 0usize;
+    s.rets
+        .iter()
+        .map(|a| {
+            let size = match a {
+                Arg::I64 | Arg::F64 => 8,
+                _ => 4,
+            };
+            offset = offset.next_multiple_of(size);
+            let field = offset;
+            offset += size;
+            field
+        })
+        .collect()
+}
+
+/// Returns the `take` modifier of `a`, if it is a resource.
+fn take(a: &Arg) -> Option<bool> {
+    match a {
+        Arg::Resource { take, .. } => Some(*take),
+        _ => None,
+    }
+}
+
+impl TsOpts {
+    /// Generates the runtime shared by the glue and RPC proxies of all interfaces.
+    ///
+    /// This should be generated once, alongside the output of [`TsOpts::glue`] and
+    /// [`TsOpts::rpc`]. It defines `PitRuntime`, holding the instance and the handles
+    /// of host values held by the guest, `PitConnection`, holding the objects served
+    /// over a port, the `PitHandles` table, and the `pitDispose` and `pitAsyncDispose`
+    /// keys, under which wrappers and proxies are disposable with `using` declarations.
+    pub fn runtime(&self) -> String {
+        format!("{RUNTIME}{}", crate::rpc::RUNTIME)
+    }
+
+    /// Generates the JavaScript glue connecting a PIT interface to a wasm instance.
+    ///
+    /// The generated code defines:
+    /// - `importsForP<hex>(rt)`, returning the `pit/<hex>` import module, which
+    ///   dispatches calls from the guest to the host values in `rt.handles`
+    /// - `wrapExportP<hex>(rt, slot)`, returning an object of type `P<hex>`
+    ///   calling the guest object in `slot`, which releases the slot when disposed
+    ///   of with `[pitDispose]()` or garbage collected
+    ///
+    /// Host values passed as owned resources belong to the guest, and are disposed
+    /// of with `[pitDispose]()`, if they have it, when the guest releases them.
+    /// Host values returned as borrowed resources are only held by the guest until
+    /// their borrow ends, when it releases their handle without disposing of them.
+    /// Calls through wasm are synchronous, so the glue always uses the synchronous
+    /// type, even when [`TsOpts::async`] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - The PIT interface
+    /// * `salt` - The salt identifying the guest's table, as passed to the guest glue
+    ///
+    /// # Returns
+    ///
+    /// A string containing the TypeScript functions; see [`TsOpts::runtime`].
+    pub fn glue(&self, i: &Interface, salt: &str) -> String {
+        let opts = TsOpts {
+            r#async: false,
+            ..self.clone()
+        };
+        let this = i.rid();
+        let h = hex::encode(this);
+        let imports = i
+            .methods
+            .iter()
+            .map(|(m, s)| {
+                let sret = s.rets.len() > 1;
+                let params = sret
+                    .then(|| String::from("ret: number"))
+                    .into_iter()
+                    .chain([String::from("handle: number")])
+                    .chain(s.params.iter().enumerate().map(|(a, b)| {
+                        format!(
+                            "p{a}: {}",
+                            match b {
+                                Arg::I64 => "bigint",
+                                _ => "number",
+                            }
+                        )
+                    }))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let args = s
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(a, b)| match take(b) {
+                        Some(true) => format!("rt.lift(p{a}) as {}", opts.ty(b, this)),
+                        Some(false) => format!("rt.borrow(p{a}) as {}", opts.ty(b, this)),
+                        None => format!("p{a}"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let lower = |a: &Arg, v: &str| match take(a) {
+                    Some(take) => format!("rt.lower({v}, {take})"),
+                    None => String::from(v),
+                };
+                let call = format!("(rt.borrow(handle) as P{h}).P{h}_{m}({args})");
+                let body = match s.rets.len() {
+                    0 => format!("        {call};\n"),
+                    1 => format!(
+                        "        const r = {call};\n        return {};\n",
+                        lower(&s.rets[0], "r[0]")
+                    ),
+                    _ => format!(
+                        "        const r = {call};\n        const v = rt.view();\n{}",
+                        s.rets
+                            .iter()
+                            .zip(layout(s))
+                            .enumerate()
+                            .map(|(n, (a, o))| format!(
+                                "        v.set{}(ret + {o}, {}, true);\n",
+                                view_ty(a),
+                                lower(a, &format!("r[{n}]"))
+                            ))
+                            .collect::<String>()
+                    ),
+                };
+                let rty = match s.rets.len() {
+                    1 => match &s.rets[0] {
+                        Arg::I64 => "bigint",
+                        _ => "number",
+                    },
+                    _ => "void",
+                };
+                format!("      {m}: ({params}): {rty} => {{\n{body}      }},\n")
+            })
+            .collect::<String>();
+        let exports = i
+            .methods
+            .iter()
+            .map(|(m, s)| {
+                let sret = s.rets.len() > 1;
+                let lent = s
+                    .params
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, b)| take(b) == Some(false))
+                    .map(|(a, _)| a)
+                    .collect::<Vec<_>>();
+                let lend = lent
+                    .iter()
+                    .map(|a| format!("      const h{a} = rt.lower(p{a}, false);\n"))
+                    .collect::<String>();
+                let args = sret
+                    .then(|| String::from("ret"))
+                    .into_iter()
+                    .chain([String::from("slot")])
+                    .chain(s.params.iter().enumerate().map(|(a, b)| match take(b) {
+                        Some(true) => format!("rt.lower(p{a}, true)"),
+                        Some(false) => format!("h{a}"),
+                        None => format!("p{a}"),
+                    }))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let lift = |a: &Arg, v: &str| match take(a) {
+                    Some(_) => format!("rt.lift({v}) as {}", opts.ty(a, this)),
+                    None => String::from(v),
+                };
+                let call = format!("rt.export(\"pit/{h}/~{salt}/{m}\")({args})");
+                let body = match s.rets.len() {
+                    0 => vec![format!("{call};"), format!("return [];")],
+                    1 => vec![
+                        format!("const r = {call};"),
+                        format!("return [{}];", lift(&s.rets[0], "r")),
+                    ],
+                    _ => vec![
+                        format!("const ret = rt.scratch();"),
+                        format!("{call};"),
+                        format!("const v = rt.view();"),
+                        format!(
+                            "return [{}];",
+                            s.rets
+                                .iter()
+                                .zip(layout(s))
+                                .map(|(a, o)| lift(
+                                    a,
+                                    &format!("v.get{}(ret + {o}, true)", view_ty(a))
+                                ))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    ],
+                };
+                let body = match lent.as_slice() {
+                    [] => body
+                        .iter()
+                        .map(|l| format!("      {l}\n"))
+                        .collect::<String>(),
+                    lent => format!(
+                        "      try {{\n{}      }} finally {{\n{}      }}\n",
+                        body.iter()
+                            .map(|l| format!("        {l}\n"))
+                            .collect::<String>(),
+                        lent.iter()
+                            .map(|a| format!("        rt.drop(h{a});\n"))
+                            .collect::<String>()
+                    ),
+                };
+                format!(
+                    "    P{h}_{m}{}{{\n{lend}{body}    }},\n",
+                    opts.meth(s, this)
+                )
+            })
+            .collect::<String>();
+        format!(
+            "/** Returns the `pit/{h}` import module, dispatching to the host values of `rt`. */
+export function importsForP{h}(rt: PitRuntime): Record<string, Record<string, Function>> {{
+  return {{
+    \"pit/{h}\": {{
+{imports}      \"~drop\": (handle: number): void => rt.drop(handle),
+      \"~{salt}\": (slot: number): number => rt.lower(wrapExportP{h}(rt, slot), true),
+    }},
+  }};
+}}
+/** Wraps the guest object in `slot`, owning the slot. */
+export function wrapExportP{h}(rt: PitRuntime, slot: number): P{h} {{
+  return rt.track({{
+{exports}  }}, () => rt.export(\"pit/{h}/~{salt}.drop\")(slot));
+}}
+"
+        )
+    }
+}
@@ -0,0 +1,105 @@
+//! Runtime type guards and argument validators.
+
+use alloc::{format, string::String, vec::Vec};
+use pit_core::{Arg, Interface};
+
+use crate::TsOpts;
+
+/// Returns a JavaScript condition checking that `v` is a value of type `a`, and
+/// a description of the type for error messages.
+fn check(a: &Arg, v: &str) -> (String, &'static str) {
+    match a {
+        Arg::I32 => (
+            format!(
+                "typeof {v} === \"number\" && Number.isInteger({v}) && {v} >= -0x80000000 && {v} <= 0xffffffff"
+            ),
+            "an I32",
+        ),
+        Arg::I64 => (
+            format!("typeof {v} === \"bigint\" && {v} >= -(1n << 63n) && {v} < 1n << 64n"),
+            "an I64",
+        ),
+        Arg::F32 => (format!("typeof {v} === \"number\""), "an F32"),
+        Arg::F64 => (format!("typeof {v} === \"number\""), "an F64"),
+        Arg::Resource { nullable, .. } => {
+            let c = format!(
+                "(typeof {v} === \"object\" && {v} !== null) || typeof {v} === \"function\""
+            );
+            match nullable {
+                true => (
+                    format!("{v} === undefined || {c}"),
+                    "a resource or undefined",
+                ),
+                false => (c, "a resource"),
+            }
+        }
+        // Values of other types, such as those of optional `pit-core` features, are
+        // accepted unchecked.
+        _ => ("true".into(), "a value"),
+    }
+}
+
+impl TsOpts {
+    /// Generates runtime checks for values of the type of a PIT interface.
+    ///
+    /// The generated code defines:
+    /// - `isP<hex>(x): x is P<hex>`, checking that `x` has every method of the interface
+    /// - `validateP<hex>_<method>(p0, ...)` for each method, throwing a `TypeError`
+    ///   unless its arguments are valid: integers in the `u32` or `i32` range for
+    ///   `I32`, `bigint`s in the `u64` or `i64` range for `I64`, numbers for floats,
+    ///   and objects for resources, `undefined` being allowed only for nullable ones
+    ///
+    /// Guards only check the shape of values, not the types of their methods, and
+    /// validators do not check the interfaces of resources, nor arguments of types other
+    /// than the ones above. Both are meant for values crossing untrusted boundaries, or
+    /// for debug builds.
+    ///
+    /// In async mode, the names are those of the async type (`isAP<hex>`).
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - The PIT interface
+    ///
+    /// # Returns
+    ///
+    /// A string containing the TypeScript functions.
+    pub fn guards(&self, i: &Interface) -> String {
+        let m = match self.r#async {
+            true => "A",
+            false => "",
+        };
+        let t = format!("{m}P{}", hex::encode(i.rid()));
+        let keys = i
+            .methods
+            .keys()
+            .map(|a| format!(" && typeof (x as any).{t}_{a} === \"function\""))
+            .collect::<String>();
+        let validators = i
+            .methods
+            .iter()
+            .map(|(a, s)| {
+                let params = (0..s.params.len())
+                    .map(|p| format!("p{p}: unknown"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let checks = s
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(p, b)| {
+                        let (c, d) = check(b, &format!("p{p}"));
+                        format!(
+                            "  if (!({c})) throw new TypeError(\"{t}_{a}: p{p} must be {d}\");\n"
+                        )
+                    })
+                    .collect::<String>();
+                format!(
+                    "/** Throws a `TypeError` unless the arguments are valid for `{t}_{a}`. */\nexport function validate{t}_{a}({params}): void {{\n{checks}}}\n"
+                )
+            })
+            .collect::<String>();
+        format!(
+            "/** Returns whether `x` has the methods of `{t}`. */\nexport function is{t}(x: unknown): x is {t} {{\n  return ((typeof x === \"object\" && x !== null) || typeof x === \"function\"){keys};\n}}\n{validators}"
+        )
+    }
+}
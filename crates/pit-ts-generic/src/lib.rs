@@ -8,9 +8,15 @@
 //! ## Overview
 //!
 //! The main type is [`TsOpts`] which provides methods for generating:
+//! - [`TsOpts::file`] - Complete TypeScript file, importing referenced interfaces
 //! - [`TsOpts::interface`] - Complete TypeScript type definition
 //! - [`TsOpts::meth`] - Method signature
 //! - [`TsOpts::ty`] - Type expression
+//! - [`TsOpts::guards`] - Runtime type guard and argument validators
+//! - [`TsOpts::glue`] - JavaScript glue connecting an interface to a wasm instance,
+//!   relying on the shared [`TsOpts::runtime`]
+//! - [`TsOpts::rpc`] - RPC proxies over `MessagePort`-like channels, also relying on
+//!   the shared [`TsOpts::runtime`]
 //!
 //! ## Example
 //!
@@ -29,6 +35,62 @@
 //! When `async` is enabled, the generated types include `| Promise<[...]>` return
 //! types and type names are prefixed with `A` (e.g., `AP<hex_id>`).
 //!
+//! ## Classes
+//!
+//! With [`TsOpts::classes`], each interface also gets an abstract base implementing
+//! `Disposable`, which owned resources are typed as, so they can be released with
+//! `using` declarations, which require `Symbol.dispose` to be defined. Borrowed
+//! resources keep the plain type:
+//!
+//! ```typescript
+//! class Impl extends P<hex_id>Base {
+//!   P<hex_id>_methodName(p0: number): [number] { return [p0]; }
+//!   [Symbol.dispose]() { /* release resources */ }
+//! }
+//! using x = factory.P<other_hex_id>_make()[0];
+//! ```
+//!
+//! Wrappers generated by [`TsOpts::glue`] and proxies generated by [`TsOpts::rpc`]
+//! are disposable both synchronously and asynchronously.
+//!
+//! ## Cross-Module References
+//!
+//! Interfaces referenced as resources are named `P<hex_id>` in the generated types.
+//! By default they are expected in the same file; [`TsOpts::rewrites`] maps them to
+//! the modules exporting them instead, which [`TsOpts::file`] imports:
+//!
+//! ```typescript
+//! import type { P<hex_id> } from "./other";
+//! ```
+//!
+//! ## Wasm Glue
+//!
+//! The glue generated by [`TsOpts::glue`] follows the guest ABI of `pit-rust-generic`.
+//! The import modules of all interfaces used by a module are merged into its imports,
+//! and the instance is set on the runtime once instantiated:
+//!
+//! ```typescript
+//! const rt = new PitRuntime();
+//! const { instance } = await WebAssembly.instantiate(bytes, { ...importsForP<hex>(rt) });
+//! rt.instance = instance;
+//! ```
+//!
+//! Host values are handed to the guest with `rt.lower(value, true)`, and guest
+//! objects are wrapped with `wrapExportP<hex>(rt, slot)`.
+//!
+//! ## RPC
+//!
+//! The proxies generated by [`TsOpts::rpc`] connect the two ends of a port, such as
+//! the one of a Web Worker:
+//!
+//! ```typescript
+//! // In the worker
+//! serveP<hex>(self, new Plugin());
+//! // In the page
+//! const plugin = proxyP<hex>(worker);
+//! const [n] = await plugin.AP<hex>_methodName(1);
+//! ```
+//!
 //! ## Features
 //!
 //! - `unstable-sdk` - Enable portal-solutions-sdk integration
@@ -36,9 +98,17 @@
 //! - `unstable-generics` - Enable generic parameter support
 
 #![no_std]
-use alloc::{collections::btree_map::BTreeMap, format, string::String, vec::Vec};
+use alloc::{
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    format,
+    string::String,
+    vec::Vec,
+};
 use pit_core::{Arg, Interface, Sig};
 extern crate alloc;
+mod glue;
+mod guard;
+mod rpc;
 
 /// Configuration options for TypeScript code generation.
 #[derive(Default, Clone, Debug)]
@@ -50,7 +120,26 @@ pub struct TsOpts {
     /// - Type names are prefixed with `A` (e.g., `AP<hex_id>`)
     /// - Return types include `| Promise<[...]>` variant
     pub r#async: bool,
-    // pub rewrites: BTreeMap<[u8; 32], String>,
+    /// Module rewrites for cross-module references.
+    ///
+    /// Maps 32-byte resource IDs to TypeScript module specifiers.
+    /// When a resource type references another interface, this map
+    /// determines which module [`TsOpts::file`] imports it from.
+    pub rewrites: BTreeMap<[u8; 32], String>,
+    /// Generate `abstract class` bases for owned resources.
+    ///
+    /// When `true`:
+    /// - [`TsOpts::interface`] also generates `abstract class P<hex_id>Base`, implementing
+    ///   the type and `Disposable` (`AsyncDisposable` in async mode) with a no-op
+    ///   `[Symbol.dispose]()` (`[Symbol.asyncDispose]()`) to override
+    /// - Owned resources are typed as the base, so they can be bound with `using`
+    ///   (`await using`), while borrowed resources keep the plain type
+    ///
+    /// As with `using`, the bases require `Symbol.dispose` (`Symbol.asyncDispose`) to
+    /// be defined, natively or by a polyfill: the `pitDispose` (`pitAsyncDispose`) key
+    /// of [`TsOpts::runtime`] only falls back to `Symbol.for("Symbol.dispose")` without
+    /// it, under which the glue would not find the methods of the bases.
+    pub classes: bool,
 }
 impl TsOpts {
     /// Converts a PIT argument type to its TypeScript type representation.
@@ -63,7 +152,8 @@ impl TsOpts {
     /// # Returns
     ///
     /// A string containing the TypeScript type (e.g., `number`, `bigint`, `any`, `P<hex_id>`).
-    /// Nullable types are rendered as `T | undefined`.
+    /// Nullable types are rendered as `T | undefined`. Owned resources are rendered as
+    /// `P<hex_id>Base` when [`TsOpts::classes`] is set.
     pub fn ty(&self, t: &Arg, this: [u8; 32]) -> String {
         let m = match self.r#async {
             true => "A",
@@ -81,8 +171,8 @@ impl TsOpts {
                 ann,
             } => match match ty {
                 pit_core::ResTy::None => format!("any"),
-                pit_core::ResTy::Of(a) => format!("{m}P{}", hex::encode(a)),
-                pit_core::ResTy::This => format!("{m}P{}", hex::encode(this)),
+                pit_core::ResTy::Of(a) => format!("{m}P{}{}", hex::encode(a), self.base(*take)),
+                pit_core::ResTy::This => format!("{m}P{}{}", hex::encode(this), self.base(*take)),
                 _ => todo!(),
             } {
                 ty => {
@@ -97,6 +187,15 @@ impl TsOpts {
         }
     }
 
+    /// Returns the suffix of the type name of a resource, `Base` if it is owned and
+    /// [`TsOpts::classes`] is set.
+    fn base(&self, take: bool) -> &'static str {
+        match self.classes && take {
+            true => "Base",
+            false => "",
+        }
+    }
+
     /// Generates a TypeScript method signature from a PIT method signature.
     ///
     /// # Arguments
@@ -143,12 +242,13 @@ impl TsOpts {
     ///
     /// # Returns
     ///
-    /// A string containing the complete TypeScript type definition.
+    /// A string containing the complete TypeScript type definition, followed by
+    /// the abstract base class if [`TsOpts::classes`] is set.
     ///
     /// # Example Output
     ///
     /// ```typescript
-    /// export type P<hex_id> = {P<hex_id>_methodName (p0: number): [number]}
+    /// export type P<hex_id> = {P<hex_id>_first (p0: number): [number]; P<hex_id>_second (): []}
     /// ```
     pub fn interface(&self, i: &Interface) -> String {
         let this = i.rid();
@@ -156,14 +256,90 @@ impl TsOpts {
             true => "A",
             false => "",
         };
-        format!(
+        let t = format!(
             "export type {m}P{} = {{{}}}",
             hex::encode(this),
             i.methods
                 .iter()
                 .map(|(a, b)| format!("{m}P{}_{a} {}", hex::encode(this), self.meth(b, this)))
                 .collect::<Vec<_>>()
-                .join("")
+                .join("; ")
+        );
+        if !self.classes {
+            return t;
+        }
+        let dispose = match self.r#async {
+            true => "AsyncDisposable {\n  async [Symbol.asyncDispose](): Promise<void> {}\n",
+            false => "Disposable {\n  [Symbol.dispose](): void {}\n",
+        };
+        format!(
+            "{t}\n/** A base for owned `{m}P{0}` resources, disposed of when released. */\nexport abstract class {m}P{0}Base implements {m}P{0}, {dispose}{1}}}",
+            hex::encode(this),
+            i.methods
+                .iter()
+                .map(|(a, b)| format!(
+                    "  abstract {m}P{}_{a}{};\n",
+                    hex::encode(this),
+                    self.meth(b, this)
+                ))
+                .collect::<String>()
         )
     }
+
+    /// Generates a complete TypeScript file from PIT interfaces.
+    ///
+    /// The file starts with an `import type` declaration per module of
+    /// [`TsOpts::rewrites`], for the interfaces referenced as resources but not
+    /// defined in the file, followed by the type definitions of `is`.
+    ///
+    /// # Arguments
+    ///
+    /// * `is` - The PIT interfaces to convert
+    ///
+    /// # Returns
+    ///
+    /// A string containing the TypeScript file.
+    ///
+    /// # Example Output
+    ///
+    /// ```typescript
+    /// import type { P<other_hex_id> } from "./other";
+    /// export type P<hex_id> = {P<hex_id>_methodName (p0: P<other_hex_id>): [number]}
+    /// ```
+    pub fn file(&self, is: &[Interface]) -> String {
+        let m = match self.r#async {
+            true => "A",
+            false => "",
+        };
+        let local = is.iter().map(|i| i.rid()).collect::<BTreeSet<_>>();
+        let mut imports: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+        for s in is.iter().flat_map(|i| i.methods.values()) {
+            for a in s.params.iter().chain(s.rets.iter()) {
+                if let Arg::Resource {
+                    ty: pit_core::ResTy::Of(r),
+                    take,
+                    ..
+                } = a
+                    && !local.contains(r)
+                    && let Some(module) = self.rewrites.get(r)
+                {
+                    imports.entry(module).or_default().insert(format!(
+                        "{m}P{}{}",
+                        hex::encode(r),
+                        self.base(*take)
+                    ));
+                }
+            }
+        }
+        imports
+            .iter()
+            .map(|(module, names)| {
+                format!(
+                    "import type {{ {} }} from {module:?};\n",
+                    names.iter().cloned().collect::<Vec<_>>().join(", ")
+                )
+            })
+            .chain(is.iter().map(|i| format!("{}\n", self.interface(i))))
+            .collect()
+    }
 }
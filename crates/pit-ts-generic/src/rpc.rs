@@ -0,0 +1,338 @@
+//! RPC proxies over `MessagePort`-like channels.
+//!
+//! Both ends of a port hold a `PitConnection`, which serves local objects to the
+//! other end and proxies the objects it serves. A call posts
+//! `{ t: "call", id, obj, method, args }`, answered by `{ t: "ret", id, rets }` or
+//! `{ t: "err", id, message }`. `obj` is either the name of a root object, as
+//! served by `serveP<hex>`, or the ID of an exported object.
+//!
+//! Resources are sent as the non-zero ID of an exported object, `0` denoting
+//! `undefined`. Each ID sent counts as one reference, held by a proxy on the other
+//! end, which posts `{ t: "drop", obj }` when disposed with `[pitDispose]()` or
+//! garbage collected. Proxies of borrowed resources are disposed once the call
+//! returns. An object is unexported when its last reference is dropped, and
+//! disposed then if it was ever sent as an owned resource. Calls on unknown or
+//! stale IDs fail, and drops of them are ignored.
+
+use alloc::{format, string::String, vec::Vec};
+use pit_core::{Arg, Interface};
+
+use crate::TsOpts;
+
+/// The runtime of the RPC protocol; see [`TsOpts::runtime`].
+pub(crate) const RUNTIME: &str = r#"/** A message of the PIT RPC protocol. */
+export type PitMessage =
+  | { t: "call"; id: number; obj: number | string; method: string; args: unknown[] }
+  | { t: "ret"; id: number; rets: unknown[] }
+  | { t: "err"; id: number; message: string }
+  | { t: "drop"; obj: number };
+/** A `MessagePort`-like channel, such as a DOM or `worker_threads` `MessagePort`. */
+export type PitPort = {
+  postMessage(message: PitMessage): void;
+  on?(type: "message", listener: (data: any) => void): unknown;
+  addEventListener?(type: "message", listener: (event: { data: any }) => void): void;
+  start?(): void;
+};
+/** Calls `method` on `target` with the arguments of a PIT RPC call, returning its results. */
+export type PitDispatch = (conn: PitConnection, target: any, method: string, args: any[]) => Promise<unknown[]>;
+/** An object exported over a `PitConnection`. */
+type PitExport = { value: object; dispatch: PitDispatch; refs: number; owned: boolean };
+const pitConnections: WeakMap<PitPort, PitConnection> = new WeakMap();
+/** One end of a port carrying PIT RPC calls. */
+export class PitConnection {
+  readonly port: PitPort;
+  #roots: Map<string, [object, PitDispatch]> = new Map();
+  #exports: PitHandles = new PitHandles();
+  #ids: Map<object, number> = new Map();
+  #calls: Map<number, [(rets: unknown[]) => void, (e: Error) => void]> = new Map();
+  #next = 0;
+  #registry: FinalizationRegistry<number> = new FinalizationRegistry<number>((obj) => this.#post({ t: "drop", obj }));
+  constructor(port: PitPort) {
+    this.port = port;
+    if (port.on !== undefined) {
+      port.on("message", (data) => this.#receive(data));
+    } else if (port.addEventListener !== undefined) {
+      port.addEventListener("message", (event) => this.#receive(event.data));
+      port.start?.();
+    } else {
+      throw new TypeError("PIT port can neither `on` nor `addEventListener`");
+    }
+  }
+  /** Returns the connection of `port`, creating it on first use. */
+  static of(port: PitPort): PitConnection {
+    let conn = pitConnections.get(port);
+    if (conn === undefined) {
+      conn = new PitConnection(port);
+      pitConnections.set(port, conn);
+    }
+    return conn;
+  }
+  #post(message: PitMessage): void {
+    this.port.postMessage(message);
+  }
+  /** Serves `value` as the root object `name`. */
+  serve(name: string, value: object, dispatch: PitDispatch): void {
+    this.#roots.set(name, [value, dispatch]);
+  }
+  /** Calls `method` on the remote object `obj`, returning its results. */
+  call(obj: number | string, method: string, args: unknown[]): Promise<unknown[]> {
+    const id = this.#next++;
+    return new Promise((resolve, reject) => {
+      this.#calls.set(id, [resolve, reject]);
+      this.#post({ t: "call", id, obj, method, args });
+    });
+  }
+  /** Exports `value` for one more remote reference, returning its ID or `0` for `undefined`. */
+  export(value: object | undefined, dispatch: PitDispatch | undefined, owned: boolean): number {
+    if (value === undefined) return 0;
+    if (dispatch === undefined) throw new TypeError("untyped resources cannot cross the PIT RPC boundary");
+    const id = this.#ids.get(value);
+    if (id !== undefined) {
+      const e = this.#exports.get(id) as PitExport;
+      e.refs++;
+      e.owned ||= owned;
+      return id;
+    }
+    const e: PitExport = { value, dispatch, refs: 1, owned };
+    const obj = this.#exports.insert(e, true);
+    this.#ids.set(value, obj);
+    return obj;
+  }
+  /** Returns a proxy holding a reference to the remote object `obj`, or `undefined` for `0`. */
+  remote<T extends object>(obj: number, make: ((conn: PitConnection, obj: number) => T) | undefined): T | undefined {
+    if (obj === 0) return undefined;
+    if (make === undefined) throw new TypeError("untyped resources cannot cross the PIT RPC boundary");
+    const proxy = make(this, obj);
+    let live = true;
+    const token = {};
+    this.#registry.register(proxy, obj, token);
+    return pitDisposable(proxy, () => {
+      if (live) {
+        live = false;
+        this.#registry.unregister(token);
+        this.#post({ t: "drop", obj });
+      }
+    });
+  }
+  /** Drops the reference of `proxy`, if any. */
+  release(proxy: unknown): void {
+    (proxy as any)?.[pitDispose]?.();
+  }
+  #receive(m: PitMessage): void {
+    switch (m.t) {
+      case "call": {
+        const { id, obj, method, args } = m;
+        const target = typeof obj === "string" ? this.#roots.get(obj) : this.#target(obj);
+        (async () => {
+          if (target === undefined) throw new Error(`unknown PIT object ${obj}`);
+          return await target[1](this, target[0], method, args);
+        })().then(
+          (rets) => this.#post({ t: "ret", id, rets }),
+          (e) => this.#post({ t: "err", id, message: e instanceof Error ? e.message : String(e) }),
+        );
+        break;
+      }
+      case "ret":
+      case "err": {
+        const call = this.#calls.get(m.id);
+        if (call === undefined) break;
+        this.#calls.delete(m.id);
+        if (m.t === "ret") call[0](m.rets);
+        else call[1](new Error(m.message));
+        break;
+      }
+      case "drop": {
+        // Like calls, drops of unknown or stale ids are ignored.
+        const e = this.#entry(m.obj);
+        if (e !== undefined && --e.refs === 0) {
+          this.#exports.take(m.obj);
+          this.#ids.delete(e.value);
+          if (e.owned) ((e.value as any)[pitDispose] ?? (e.value as any)[pitAsyncDispose])?.call(e.value);
+        }
+        break;
+      }
+    }
+  }
+  #entry(obj: number): PitExport | undefined {
+    try {
+      return this.#exports.get(obj) as PitExport;
+    } catch {
+      return undefined;
+    }
+  }
+  #target(obj: number): [object, PitDispatch] | undefined {
+    const e = this.#entry(obj);
+    return e === undefined ? undefined : [e.value, e.dispatch];
+  }
+}
+"#;
+
+/// Returns the resource ID referenced by `a`, if `a` is a resource.
+///
+/// The inner `Option` is `None` for untyped resources.
+fn res(a: &Arg, this: [u8; 32]) -> Option<Option<[u8; 32]>> {
+    match a {
+        Arg::Resource { ty, .. } => Some(match ty {
+            pit_core::ResTy::Of(a) => Some(*a),
+            pit_core::ResTy::This => Some(this),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+impl TsOpts {
+    /// Generates RPC proxies for a PIT interface over `MessagePort`-like channels.
+    ///
+    /// The generated code defines:
+    /// - `serveP<hex>(port, impl)`, serving `impl` to the other end of `port`
+    /// - `proxyP<hex>(port)`, returning an object of type `AP<hex>` calling the
+    ///   object served by the other end of `port`
+    /// - `dispatchP<hex>` and `remoteP<hex>`, used for resources of the interface
+    ///
+    /// Ports only need `postMessage` and either `on` or `addEventListener`, so both
+    /// DOM and `worker_threads` ports work. Resources are passed as remote proxies,
+    /// released when disposed of with `[pitDispose]()` or garbage collected.
+    /// Calls are asynchronous, so the code always uses the async types, even when
+    /// [`TsOpts::async`] is not set.
+    ///
+    /// Every interface referenced as a resource must also have its proxies generated
+    /// in the same file.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - The PIT interface
+    ///
+    /// # Returns
+    ///
+    /// A string containing the TypeScript functions; see [`TsOpts::runtime`].
+    pub fn rpc(&self, i: &Interface) -> String {
+        let opts = TsOpts {
+            r#async: true,
+            ..self.clone()
+        };
+        let this = i.rid();
+        let h = hex::encode(this);
+        let export = |a: &Arg, v: &str, owned: bool| match res(a, this) {
+            Some(Some(r)) => format!("conn.export({v}, dispatchP{}, {owned})", hex::encode(r)),
+            Some(None) => format!("conn.export({v}, undefined, {owned})"),
+            None => String::from(v),
+        };
+        let remote = |a: &Arg, v: &str| match res(a, this) {
+            Some(Some(r)) => format!(
+                "conn.remote({v} as number, remoteP{}) as {}",
+                hex::encode(r),
+                opts.ty(a, this)
+            ),
+            Some(None) => format!("conn.remote({v} as number, undefined)"),
+            None => format!("{v} as {}", opts.ty(a, this)),
+        };
+        let owned = |a: &Arg| !matches!(a, Arg::Resource { take: false, .. });
+        let dispatch = i
+            .methods
+            .iter()
+            .map(|(m, s)| {
+                let lifted = s
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(a, b)| {
+                        format!("      const p{a} = {};\n", remote(b, &format!("args[{a}]")))
+                    })
+                    .collect::<String>();
+                let args = (0..s.params.len())
+                    .map(|a| format!("p{a}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let rets = s
+                    .rets
+                    .iter()
+                    .enumerate()
+                    .map(|(a, b)| export(b, &format!("r[{a}]"), owned(b)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let call = match s.rets.len() {
+                    0 => format!("await target.AP{h}_{m}({args});\n        return [];\n"),
+                    _ => format!(
+                        "const r = await target.AP{h}_{m}({args});\n        return [{rets}];\n"
+                    ),
+                };
+                let lent = s
+                    .params
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, b)| res(b, this).is_some() && !owned(b))
+                    .map(|(a, _)| format!("        conn.release(p{a});\n"))
+                    .collect::<String>();
+                let body = match lent.as_str() {
+                    "" => format!("      {}", call.replace("\n        ", "\n      ")),
+                    lent => {
+                        format!("      try {{\n        {call}      }} finally {{\n{lent}      }}\n")
+                    }
+                };
+                format!("    case {m:?}: {{\n{lifted}{body}    }}\n")
+            })
+            .collect::<String>();
+        let proxies = i
+            .methods
+            .iter()
+            .map(|(m, s)| {
+                let args = s
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(a, b)| export(b, &format!("p{a}"), owned(b)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let params = s
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(a, b)| format!("p{a}: {}", opts.ty(b, this)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let rets = s
+                    .rets
+                    .iter()
+                    .map(|b| opts.ty(b, this))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let lifted = s
+                    .rets
+                    .iter()
+                    .enumerate()
+                    .map(|(a, b)| remote(b, &format!("r[{a}]")))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let call = format!("await conn.call(obj, {m:?}, [{args}]);");
+                let body = match s.rets.len() {
+                    0 => format!("      {call}\n      return [];\n"),
+                    _ => format!("      const r = {call}\n      return [{lifted}];\n"),
+                };
+                format!("    async AP{h}_{m}({params}): Promise<[{rets}]> {{\n{body}    }},\n")
+            })
+            .collect::<String>();
+        format!(
+            "/** Serves `impl` as `P{h}` to the other end of `port`. */
+export function serveP{h}(port: PitPort, impl: AP{h}): void {{
+  PitConnection.of(port).serve(\"P{h}\", impl, dispatchP{h});
+}}
+/** Returns a proxy of the `P{h}` served by the other end of `port`. */
+export function proxyP{h}(port: PitPort): AP{h} {{
+  return remoteP{h}(PitConnection.of(port), \"P{h}\");
+}}
+/** Calls `method` on `target` with the arguments of a PIT RPC call, returning its results. */
+export async function dispatchP{h}(conn: PitConnection, target: AP{h}, method: string, args: unknown[]): Promise<unknown[]> {{
+  switch (method) {{
+{dispatch}  }}
+  throw new Error(`unknown method ${{method}} of P{h}`);
+}}
+/** Returns a proxy of the remote `P{h}` object `obj`. */
+export function remoteP{h}(conn: PitConnection, obj: number | string): AP{h} {{
+  return {{
+{proxies}  }};
+}}
+"
+        )
+    }
+}
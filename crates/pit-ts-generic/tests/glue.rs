@@ -0,0 +1,50 @@
+//! Snapshot tests for [`TsOpts::glue`].
+//!
+//! The interface has a method taking an `I64`, a nullable, an owned and a borrowed
+//! resource, and returning an `I64` and a borrowed resource. Its resource ID is
+//! replaced with `<hex>` in the output.
+
+use pit_ts_generic::TsOpts;
+
+const PIT: &str = "{pass(I64,Rthisn,Rthis,Rthis&) -> (I64,Rthis&)}";
+
+#[test]
+fn glue() {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let o = TsOpts::default();
+    assert_eq!(
+        o.glue(&i, "salt").replace(&hex::encode(i.rid()), "<hex>"),
+        r#"/** Returns the `pit/<hex>` import module, dispatching to the host values of `rt`. */
+export function importsForP<hex>(rt: PitRuntime): Record<string, Record<string, Function>> {
+  return {
+    "pit/<hex>": {
+      pass: (ret: number, handle: number, p0: bigint, p1: number, p2: number, p3: number): void => {
+        const r = (rt.borrow(handle) as P<hex>).P<hex>_pass(p0, rt.lift(p1) as P<hex> | undefined, rt.lift(p2) as P<hex>, rt.borrow(p3) as P<hex>);
+        const v = rt.view();
+        v.setBigInt64(ret + 0, r[0], true);
+        v.setInt32(ret + 8, rt.lower(r[1], false), true);
+      },
+      "~drop": (handle: number): void => rt.drop(handle),
+      "~salt": (slot: number): number => rt.lower(wrapExportP<hex>(rt, slot), true),
+    },
+  };
+}
+/** Wraps the guest object in `slot`, owning the slot. */
+export function wrapExportP<hex>(rt: PitRuntime, slot: number): P<hex> {
+  return rt.track({
+    P<hex>_pass(p0: bigint,p1: P<hex> | undefined,p2: P<hex>,p3: P<hex>): [bigint,P<hex>]{
+      const h3 = rt.lower(p3, false);
+      try {
+        const ret = rt.scratch();
+        rt.export("pit/<hex>/~salt/pass")(ret, slot, p0, rt.lower(p1, true), rt.lower(p2, true), h3);
+        const v = rt.view();
+        return [v.getBigInt64(ret + 0, true), rt.lift(v.getInt32(ret + 8, true)) as P<hex>];
+      } finally {
+        rt.drop(h3);
+      }
+    },
+  }, () => rt.export("pit/<hex>/~salt.drop")(slot));
+}
+"#
+    );
+}
@@ -0,0 +1,30 @@
+//! Snapshot tests for [`TsOpts::guards`].
+//!
+//! The interface has a method taking an `I64`, a nullable, an owned and a borrowed
+//! resource, and returning an `I64` and a borrowed resource. Its resource ID is
+//! replaced with `<hex>` in the output.
+
+use pit_ts_generic::TsOpts;
+
+const PIT: &str = "{pass(I64,Rthisn,Rthis,Rthis&) -> (I64,Rthis&)}";
+
+#[test]
+fn guards() {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let o = TsOpts::default();
+    assert_eq!(
+        o.guards(&i).replace(&hex::encode(i.rid()), "<hex>"),
+        r#"/** Returns whether `x` has the methods of `P<hex>`. */
+export function isP<hex>(x: unknown): x is P<hex> {
+  return ((typeof x === "object" && x !== null) || typeof x === "function") && typeof (x as any).P<hex>_pass === "function";
+}
+/** Throws a `TypeError` unless the arguments are valid for `P<hex>_pass`. */
+export function validateP<hex>_pass(p0: unknown, p1: unknown, p2: unknown, p3: unknown): void {
+  if (!(typeof p0 === "bigint" && p0 >= -(1n << 63n) && p0 < 1n << 64n)) throw new TypeError("P<hex>_pass: p0 must be an I64");
+  if (!(p1 === undefined || (typeof p1 === "object" && p1 !== null) || typeof p1 === "function")) throw new TypeError("P<hex>_pass: p1 must be a resource or undefined");
+  if (!((typeof p2 === "object" && p2 !== null) || typeof p2 === "function")) throw new TypeError("P<hex>_pass: p2 must be a resource");
+  if (!((typeof p3 === "object" && p3 !== null) || typeof p3 === "function")) throw new TypeError("P<hex>_pass: p3 must be a resource");
+}
+"#
+    );
+}
@@ -0,0 +1,65 @@
+//! Tests for the text of the types generated by [`TsOpts::interface`] and [`TsOpts::file`].
+
+use pit_ts_generic::TsOpts;
+
+#[test]
+fn members() {
+    let (_, i) = pit_core::parse_interface("{read8(I32) -> (I32);write8(I32,I32) -> ()}").unwrap();
+    let h = hex::encode(i.rid());
+    // Members are separated by `; `, as TypeScript requires.
+    assert_eq!(
+        TsOpts::default().interface(&i),
+        format!(
+            "export type P{h} = {{P{h}_read8 (p0: number): [number]; P{h}_write8 (p0: number,p1: number): []}}"
+        )
+    );
+    let mut o = TsOpts::default();
+    o.r#async = true;
+    assert_eq!(
+        o.interface(&i),
+        format!(
+            "export type AP{h} = {{AP{h}_read8 (p0: number): [number]| Promise<[number]>; AP{h}_write8 (p0: number,p1: number): []| Promise<[]>}}"
+        )
+    );
+}
+
+#[test]
+fn file() {
+    let (_, other) = pit_core::parse_interface("{size() -> (I32)}").unwrap();
+    let o = hex::encode(other.rid());
+    let (_, i) = pit_core::parse_interface(&format!("{{wrap(R{o}) -> (Rthis)}}")).unwrap();
+    let h = hex::encode(i.rid());
+    let mut opts = TsOpts::default();
+    opts.rewrites.insert(other.rid(), "./other".to_owned());
+    assert_eq!(
+        opts.file(&[i]),
+        format!(
+            "import type {{ P{o} }} from \"./other\";\nexport type P{h} = {{P{h}_wrap (p0: P{o}): [P{h}]}}\n"
+        )
+    );
+}
+
+#[test]
+fn classes() {
+    let (_, i) = pit_core::parse_interface("{read8(I32) -> (I32);make() -> (Rthis)}").unwrap();
+    let h = hex::encode(i.rid());
+    let mut o = TsOpts::default();
+    o.classes = true;
+    // Owned resources are typed as the base, disposed of under `Symbol.dispose`.
+    assert_eq!(
+        o.interface(&i),
+        format!(
+            "export type P{h} = {{P{h}_make (): [P{h}Base]; P{h}_read8 (p0: number): [number]}}
+/** A base for owned `P{h}` resources, disposed of when released. */
+export abstract class P{h}Base implements P{h}, Disposable {{
+  [Symbol.dispose](): void {{}}
+  abstract P{h}_make(): [P{h}Base];
+  abstract P{h}_read8(p0: number): [number];
+}}"
+        )
+    );
+    o.r#async = true;
+    assert!(o.interface(&i).contains(&format!(
+        "export abstract class AP{h}Base implements AP{h}, AsyncDisposable {{\n  async [Symbol.asyncDispose](): Promise<void> {{}}\n"
+    )));
+}
@@ -0,0 +1,54 @@
+//! Snapshot tests for [`TsOpts::rpc`].
+//!
+//! The interface has a method taking an `I64`, a nullable, an owned and a borrowed
+//! resource, and returning an `I64` and a borrowed resource. Its resource ID is
+//! replaced with `<hex>` in the output.
+
+use pit_ts_generic::TsOpts;
+
+const PIT: &str = "{pass(I64,Rthisn,Rthis,Rthis&) -> (I64,Rthis&)}";
+
+#[test]
+fn rpc() {
+    let (_, i) = pit_core::parse_interface(PIT).unwrap();
+    let o = TsOpts::default();
+    assert_eq!(
+        o.rpc(&i).replace(&hex::encode(i.rid()), "<hex>"),
+        r#"/** Serves `impl` as `P<hex>` to the other end of `port`. */
+export function serveP<hex>(port: PitPort, impl: AP<hex>): void {
+  PitConnection.of(port).serve("P<hex>", impl, dispatchP<hex>);
+}
+/** Returns a proxy of the `P<hex>` served by the other end of `port`. */
+export function proxyP<hex>(port: PitPort): AP<hex> {
+  return remoteP<hex>(PitConnection.of(port), "P<hex>");
+}
+/** Calls `method` on `target` with the arguments of a PIT RPC call, returning its results. */
+export async function dispatchP<hex>(conn: PitConnection, target: AP<hex>, method: string, args: unknown[]): Promise<unknown[]> {
+  switch (method) {
+    case "pass": {
+      const p0 = args[0] as bigint;
+      const p1 = conn.remote(args[1] as number, remoteP<hex>) as AP<hex> | undefined;
+      const p2 = conn.remote(args[2] as number, remoteP<hex>) as AP<hex>;
+      const p3 = conn.remote(args[3] as number, remoteP<hex>) as AP<hex>;
+      try {
+        const r = await target.AP<hex>_pass(p0, p1, p2, p3);
+        return [r[0], conn.export(r[1], dispatchP<hex>, false)];
+      } finally {
+        conn.release(p3);
+      }
+    }
+  }
+  throw new Error(`unknown method ${method} of P<hex>`);
+}
+/** Returns a proxy of the remote `P<hex>` object `obj`. */
+export function remoteP<hex>(conn: PitConnection, obj: number | string): AP<hex> {
+  return {
+    async AP<hex>_pass(p0: bigint, p1: AP<hex> | undefined, p2: AP<hex>, p3: AP<hex>): Promise<[bigint, AP<hex>]> {
+      const r = await conn.call(obj, "pass", [p0, conn.export(p1, dispatchP<hex>, true), conn.export(p2, dispatchP<hex>, true), conn.export(p3, dispatchP<hex>, false)]);
+      return [r[0] as bigint, conn.remote(r[1] as number, remoteP<hex>) as AP<hex>];
+    },
+  };
+}
+"#
+    );
+}